@@ -0,0 +1,74 @@
+use crate::eval::evaluate_tree;
+use crate::lexer::{is_identifier, Token};
+use crate::parse_within_limits;
+use crate::session::{with_binding, Session};
+use crate::value::Value;
+
+// `solve(lhs = rhs, x)`: the value of `x` satisfying a linear equation.
+// Moving everything to one side gives f(x) = lhs - rhs = a*x + b, so
+// sampling f at 0, 1 and 2 yields b and a, and a second difference away
+// from zero shows the equation is not linear.
+pub(crate) fn solve(args: &[&str], session: &mut Session) -> Result<Value, String> {
+    let [equation, variable] = args else {
+        return Err("Usage: solve(lhs = rhs, variable)".to_string());
+    };
+
+    let variable = variable.trim();
+    if !is_identifier(variable) || session.is_constant(variable) {
+        return Err(format!("Invalid variable '{}'", variable));
+    }
+    let (lhs, rhs) =
+        split_equation(equation).ok_or_else(|| "Usage: solve(lhs = rhs, variable)".to_string())?;
+    let lhs = parse_within_limits(lhs, session)?;
+    let rhs = parse_within_limits(rhs, session)?;
+
+    let mut samples = [0.0; 3];
+    for (x, sample) in samples.iter_mut().enumerate() {
+        *sample = with_binding(session, variable, Value::Int(x as i64), |session| {
+            difference(&lhs, &rhs, session)
+        })?;
+    }
+    let [f0, f1, f2] = samples;
+
+    let scale = samples.iter().fold(1.0, |max: f64, f| max.max(f.abs()));
+    if (f2 - 2.0 * f1 + f0).abs() > 1e-9 * scale || !scale.is_finite() {
+        return Err(format!("Equation is not linear in '{}'", variable));
+    }
+
+    let slope = f1 - f0;
+    if slope == 0.0 {
+        return Err(if f0 == 0.0 {
+            "Equation has infinitely many solutions".to_string()
+        } else {
+            "Equation has no solution".to_string()
+        });
+    }
+
+    Ok(Value::Float(-f0 / slope))
+}
+
+fn difference(lhs: &[Token], rhs: &[Token], session: &Session) -> Result<f64, String> {
+    Ok(evaluate_tree(lhs, session)?.as_f64() - evaluate_tree(rhs, session)?.as_f64())
+}
+
+// Split at the single `=` that is not part of `==`, `!=`, `<=`, `>=` or `~=`
+pub(crate) fn split_equation(equation: &str) -> Option<(&str, &str)> {
+    let bytes = equation.as_bytes();
+    let mut found = None;
+    for (index, &byte) in bytes.iter().enumerate() {
+        let before = index.checked_sub(1).map(|i| bytes[i]);
+        let after = bytes.get(index + 1).copied();
+        if byte == b'='
+            && !matches!(before, Some(b'=' | b'!' | b'<' | b'>' | b'~'))
+            && after != Some(b'=')
+        {
+            if found.is_some() {
+                return None;
+            }
+            found = Some(index);
+        }
+    }
+
+    let index = found?;
+    Some((&equation[..index], &equation[index + 1..]))
+}
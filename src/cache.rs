@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use crate::eval::constant;
+use crate::lexer::{tokenize_with, Token};
+use crate::session::Session;
+use crate::value::Value;
+
+// Least-recently-used cache of results, keyed by the trimmed input text and
+// the settings it was evaluated under
+pub(crate) struct ResultCache {
+    capacity: usize,
+    // Value and the tick at which it was last used
+    entries: HashMap<String, (Value, u64)>,
+    tick: u64,
+}
+
+impl ResultCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        ResultCache {
+            capacity,
+            entries: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    pub(crate) fn get(&mut self, key: &str) -> Option<Value> {
+        self.tick += 1;
+        let (value, last_used) = self.entries.get_mut(key)?;
+        *last_used = self.tick;
+        Some(value.clone())
+    }
+
+    pub(crate) fn insert(&mut self, key: &str, value: Value) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(key.to_string(), (value, self.tick));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+// The settings that change how an expression is read or what it evaluates
+// to, so that changing one misses rather than returning a stale result
+pub(crate) fn cache_key(expression: &str, session: &Session) -> String {
+    format!(
+        "{:?} {:?} {:?} {:?} {:?} {:?}|{}",
+        session.angle_mode,
+        session.display_mode,
+        session.decimal_scale,
+        session.tolerance,
+        (
+            session.simple_mode,
+            session.strict_paren,
+            session.auto_close
+        ),
+        (
+            session.frac_mode,
+            session.strict_mode,
+            session.complex_mode,
+            session.finance_mode,
+            session.time_mode,
+        ),
+        expression
+    )
+}
+
+// Only expressions built from literals, constants and built-in functions give the same
+// result every time; anything naming a variable or `ans` must not be cached
+pub(crate) fn is_cacheable(expression: &str, session: &Session) -> bool {
+    let Ok(tokens) = tokenize_with(expression, session) else {
+        return false;
+    };
+
+    let mut tokens = tokens.iter().peekable();
+    while let Some(token) = tokens.next() {
+        if let Token::Identifier(name) = token {
+            let is_call = matches!(tokens.peek(), Some(Token::LeftParenthesis));
+            if !is_call && constant(name).is_none() {
+                return false;
+            }
+            // A user function's body may read variables, and `random()`
+            // differs every time
+            if is_call && (session.functions.contains_key(name) || name == "random") {
+                return false;
+            }
+        }
+    }
+
+    true
+}
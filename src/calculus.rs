@@ -0,0 +1,327 @@
+use crate::evaluate_expression;
+use crate::lexer::{is_identifier, Token};
+use crate::parse_within_limits;
+use crate::session::{with_binding, Session};
+use crate::value::Value;
+
+// `diff(expr, x, at)`: derivative of `expr` with respect to `x` at `at`, by
+// central difference. The step is the cube root of machine epsilon scaled to
+// the point, which balances truncation error against rounding error and
+// leaves roughly 10 significant digits for smooth functions. Expect less
+// near discontinuities, kinks, or where the function is very steep.
+pub(crate) fn differentiate(args: &[&str], session: &mut Session) -> Result<Value, String> {
+    let [expression, variable, at] = args else {
+        return Err("Usage: diff(expr, variable, at)".to_string());
+    };
+
+    let variable = variable.trim();
+    if !is_identifier(variable) || session.is_constant(variable) {
+        return Err(format!("Invalid variable '{}'", variable));
+    }
+
+    let at = evaluate_expression(at, session)?.as_f64();
+    let step = f64::EPSILON.cbrt() * at.abs().max(1.0);
+
+    let ahead = with_binding(session, variable, Value::Float(at + step), |session| {
+        evaluate_expression(expression, session)
+    })?;
+    let behind = with_binding(session, variable, Value::Float(at - step), |session| {
+        evaluate_expression(expression, session)
+    })?;
+
+    Ok(Value::Float(
+        (ahead.as_f64() - behind.as_f64()) / (2.0 * step),
+    ))
+}
+
+// An expression as a tree, for symbolic differentiation
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Name(String),
+    Negate(Box<Expr>),
+    // One of + - * / ^
+    Binary(char, Box<Expr>, Box<Expr>),
+    // A one-argument elementary function
+    Call(String, Box<Expr>),
+}
+
+// Functions `derivative` knows the derivative of
+const DIFFERENTIABLE_FUNCTIONS: [&str; 7] = ["sin", "cos", "tan", "exp", "ln", "log", "sqrt"];
+
+// `derivative(expr, x)`: the derivative of `expr` with respect to `x`, worked
+// out symbolically and simplified. Covers + - * /, powers, and the
+// functions in DIFFERENTIABLE_FUNCTIONS; anything else is an error.
+pub(crate) fn derivative(args: &[&str], session: &Session) -> Result<String, String> {
+    let [expression, variable] = args else {
+        return Err("Usage: derivative(expr, variable)".to_string());
+    };
+
+    let variable = variable.trim();
+    if !is_identifier(variable) || session.is_constant(variable) {
+        return Err(format!("Invalid variable '{}'", variable));
+    }
+
+    let tree = parse_within_limits(expression, session)?;
+    let expression = build(&tree)?;
+    Ok(to_text(&simplify(differentiate_expr(
+        &expression,
+        variable,
+    )?)))
+}
+
+// Rebuild the tree from the parser's RPN
+fn build(tokens: &[Token]) -> Result<Expr, String> {
+    let mut stack: Vec<Expr> = Vec::new();
+    for token in tokens {
+        let expr = match token {
+            Token::Integer(n) => Expr::Number(*n as f64),
+            Token::Number(x) => Expr::Number(*x),
+            Token::Identifier(name) => Expr::Name(name.clone()),
+            Token::UnaryMinus => Expr::Negate(Box::new(stack.pop().ok_or_else(invalid)?)),
+            Token::Operator(op) if matches!(op.as_str(), "+" | "-" | "*" | "/" | "^") => {
+                let b = stack.pop().ok_or_else(invalid)?;
+                let a = stack.pop().ok_or_else(invalid)?;
+                Expr::Binary(op.chars().next().unwrap(), Box::new(a), Box::new(b))
+            }
+            Token::Function(name, 1) if DIFFERENTIABLE_FUNCTIONS.contains(&name.as_str()) => {
+                Expr::Call(name.clone(), Box::new(stack.pop().ok_or_else(invalid)?))
+            }
+            Token::Function(name, _) => {
+                return Err(format!("derivative does not support '{}('", name))
+            }
+            Token::Operator(op) => return Err(format!("derivative does not support '{}'", op)),
+            _ => {
+                return Err(
+                    "derivative supports only + - * / ^ and elementary functions".to_string(),
+                )
+            }
+        };
+        stack.push(expr);
+    }
+
+    match (stack.pop(), stack.is_empty()) {
+        (Some(expr), true) => Ok(expr),
+        _ => Err(invalid()),
+    }
+}
+
+fn invalid() -> String {
+    "Invalid expression format".to_string()
+}
+
+fn number(x: f64) -> Box<Expr> {
+    Box::new(Expr::Number(x))
+}
+
+fn binary(op: char, a: Expr, b: Expr) -> Expr {
+    Expr::Binary(op, Box::new(a), Box::new(b))
+}
+
+fn call(name: &str, argument: &Expr) -> Expr {
+    Expr::Call(name.to_string(), Box::new(argument.clone()))
+}
+
+fn contains(expr: &Expr, variable: &str) -> bool {
+    match expr {
+        Expr::Number(_) => false,
+        Expr::Name(name) => name == variable,
+        Expr::Negate(a) | Expr::Call(_, a) => contains(a, variable),
+        Expr::Binary(_, a, b) => contains(a, variable) || contains(b, variable),
+    }
+}
+
+fn differentiate_expr(expr: &Expr, x: &str) -> Result<Expr, String> {
+    let d = |expr: &Expr| differentiate_expr(expr, x);
+    Ok(match expr {
+        Expr::Number(_) => Expr::Number(0.0),
+        Expr::Name(name) => Expr::Number(if name == x { 1.0 } else { 0.0 }),
+        Expr::Negate(a) => Expr::Negate(Box::new(d(a)?)),
+        Expr::Binary(op @ ('+' | '-'), a, b) => binary(*op, d(a)?, d(b)?),
+        // Product and quotient rules
+        Expr::Binary('*', a, b) => binary(
+            '+',
+            binary('*', d(a)?, (**b).clone()),
+            binary('*', (**a).clone(), d(b)?),
+        ),
+        Expr::Binary('/', a, b) => binary(
+            '/',
+            binary(
+                '-',
+                binary('*', d(a)?, (**b).clone()),
+                binary('*', (**a).clone(), d(b)?),
+            ),
+            Expr::Binary('^', b.clone(), number(2.0)),
+        ),
+        // Power rule for a constant exponent, and a^u = a^u * ln(a) * u' for
+        // a constant base
+        Expr::Binary('^', a, b) if !contains(b, x) => binary(
+            '*',
+            binary(
+                '*',
+                (**b).clone(),
+                Expr::Binary(
+                    '^',
+                    a.clone(),
+                    Box::new(binary('-', (**b).clone(), Expr::Number(1.0))),
+                ),
+            ),
+            d(a)?,
+        ),
+        Expr::Binary('^', a, b) if !contains(a, x) => {
+            binary('*', binary('*', expr.clone(), call("ln", a)), d(b)?)
+        }
+        Expr::Binary('^', ..) => {
+            return Err("derivative needs a constant base or exponent in '^'".to_string())
+        }
+        Expr::Binary(op, ..) => return Err(format!("derivative does not support '{}'", op)),
+        // Chain rule
+        Expr::Call(name, u) => {
+            let outer = match name.as_str() {
+                "sin" => call("cos", u),
+                "cos" => Expr::Negate(Box::new(call("sin", u))),
+                "tan" => binary(
+                    '/',
+                    Expr::Number(1.0),
+                    Expr::Binary('^', Box::new(call("cos", u)), number(2.0)),
+                ),
+                "exp" => expr.clone(),
+                "ln" => binary('/', Expr::Number(1.0), (**u).clone()),
+                "log" => binary(
+                    '/',
+                    Expr::Number(1.0),
+                    binary('*', (**u).clone(), call("ln", &Expr::Number(10.0))),
+                ),
+                "sqrt" => binary(
+                    '/',
+                    Expr::Number(1.0),
+                    binary('*', Expr::Number(2.0), expr.clone()),
+                ),
+                _ => return Err(format!("derivative does not support '{}('", name)),
+            };
+            binary('*', outer, d(u)?)
+        }
+    })
+}
+
+// Fold constants and drop the zeros and ones the rules above leave behind
+fn simplify(expr: Expr) -> Expr {
+    use Expr::{Binary, Negate, Number};
+
+    match expr {
+        Negate(a) => match simplify(*a) {
+            Number(x) => Number(-x),
+            Negate(inner) => *inner,
+            Binary('*', a, b) if matches!(*a, Number(_)) => {
+                let Number(x) = *a else { unreachable!() };
+                Binary('*', number(-x), b)
+            }
+            a => Negate(Box::new(a)),
+        },
+        Expr::Call(name, a) => Expr::Call(name, Box::new(simplify(*a))),
+        Binary(op, a, b) => match (op, simplify(*a), simplify(*b)) {
+            ('+', Number(x), Number(y)) => Number(x + y),
+            ('-', Number(x), Number(y)) => Number(x - y),
+            ('*', Number(x), Number(y)) => Number(x * y),
+            // Only whole quotients, so 1/3 stays exact
+            ('/', Number(x), Number(y)) if y != 0.0 && (x / y).fract() == 0.0 => Number(x / y),
+            ('/', Number(x), Number(y)) if is_whole(x) && is_whole(y) && y != 0.0 => {
+                let divisor = gcd(x.abs(), y.abs()) * y.signum();
+                Binary('/', number(x / divisor), number(y / divisor))
+            }
+            // 8*x/16 is x/2
+            ('/', Binary('*', a, b), Number(y))
+                if is_whole_number(&a) && is_whole(y) && y != 0.0 =>
+            {
+                let Number(x) = *a else { unreachable!() };
+                let divisor = gcd(x.abs(), y.abs()) * y.signum();
+                let numerator = simplify(Binary('*', number(x / divisor), b));
+                simplify(Binary('/', Box::new(numerator), number(y / divisor)))
+            }
+            ('^', Number(x), Number(y)) if x.powf(y).fract() == 0.0 => Number(x.powf(y)),
+            ('+', Number(0.0), a) | ('+' | '-', a, Number(0.0)) => a,
+            ('-', Number(0.0), a) => simplify(Negate(Box::new(a))),
+            ('+', a, Negate(b)) => Binary('-', Box::new(a), b),
+            ('-', a, Negate(b)) => Binary('+', Box::new(a), b),
+            ('*', Number(0.0), _) | ('*', _, Number(0.0)) | ('/', Number(0.0), _) => Number(0.0),
+            ('*', Number(1.0), a) | ('*' | '/' | '^', a, Number(1.0)) => a,
+            ('^', _, Number(0.0)) => Number(1.0),
+            ('*', Number(-1.0), a) | ('*', a, Number(-1.0)) => simplify(Negate(Box::new(a))),
+            ('*', Negate(a), b) => simplify(Negate(Box::new(Binary('*', a, Box::new(b))))),
+            ('*', a, Negate(b)) => simplify(Negate(Box::new(Binary('*', Box::new(a), b)))),
+            // Coefficients go first and combine: x*2 is 2*x, 2*(3*x) is 6*x
+            ('*', a, Number(y)) => simplify(Binary('*', number(y), Box::new(a))),
+            ('*', Number(x), Binary('*', b, c)) if matches!(*b, Number(_)) => {
+                let Number(y) = *b else { unreachable!() };
+                Binary('*', number(x * y), c)
+            }
+            (op, a, b) => Binary(op, Box::new(a), Box::new(b)),
+        },
+        expr => expr,
+    }
+}
+
+fn is_whole(x: f64) -> bool {
+    x.fract() == 0.0
+}
+
+fn is_whole_number(expr: &Expr) -> bool {
+    matches!(expr, Expr::Number(x) if is_whole(*x))
+}
+
+fn gcd(a: f64, b: f64) -> f64 {
+    if b == 0.0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// How tightly each kind of node binds when printed
+fn print_precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Binary('+' | '-', ..) => 1,
+        Expr::Binary('*' | '/', ..) => 2,
+        Expr::Negate(_) => 3,
+        Expr::Number(x) if *x < 0.0 => 3,
+        Expr::Binary(..) => 4,
+        _ => 5,
+    }
+}
+
+// Write the tree back out with only the parentheses it needs
+fn to_text(expr: &Expr) -> String {
+    match expr {
+        Expr::Number(x) => x.to_string(),
+        Expr::Name(name) => name.clone(),
+        Expr::Negate(a) => format!("-{}", operand(a, 4)),
+        Expr::Call(name, a) => format!("{}({})", name, to_text(a)),
+        Expr::Binary(op, a, b) => {
+            let own = print_precedence(expr);
+            // `a - (b + c)`, `a / (b * c)` and `(a ^ b) ^ c` keep theirs
+            let (left, right) = match op {
+                '^' => (own + 1, own),
+                '-' | '/' => (own, own + 1),
+                _ => (own, own),
+            };
+            let spacing = if matches!(op, '+' | '-') { " " } else { "" };
+            format!(
+                "{}{}{}{}{}",
+                operand(a, left),
+                spacing,
+                op,
+                spacing,
+                operand(b, right)
+            )
+        }
+    }
+}
+
+// An operand printed in parentheses unless it binds at least `needed`
+fn operand(expr: &Expr, needed: u8) -> String {
+    if print_precedence(expr) < needed {
+        format!("({})", to_text(expr))
+    } else {
+        to_text(expr)
+    }
+}
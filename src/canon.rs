@@ -0,0 +1,114 @@
+use crate::eval::evaluate_tree;
+use crate::lexer::{tokenize_with, Token};
+use crate::parser::parse_tokens;
+use crate::session::Session;
+use crate::CalcError;
+
+/// Check that the parser reads its own output back the same way: parse the
+/// expression, write the result out fully parenthesized, parse that, and
+/// check that both parses have the same structure and evaluate to the same
+/// value (or fail the same way, for instance on an unknown variable).
+/// Returns the parenthesized form, or an error if the two differ.
+///
+/// ```
+/// use calc::{canonical_check, Session};
+///
+/// let session = Session::new();
+/// let check = |input| canonical_check(input, &session);
+/// assert_eq!(check("1 + 2 * 3 - 4"), Ok("((1 + (2 * 3)) - 4)".to_string()));
+/// assert_eq!(check("2 ^ 3 ^ 2"), Ok("(2 ^ (3 ^ 2))".to_string()));
+/// assert_eq!(check("-2^2"), Ok("(-(2 ^ 2))".to_string()));
+/// assert_eq!(check("8 / 4 / 2"), Ok("((8 / 4) / 2)".to_string()));
+/// assert_eq!(check("1 < 2 and 3 mod 2 == 1"), Ok("((1 < 2) and ((3 mod 2) == 1))".to_string()));
+/// assert_eq!(check("|x - 1| + max(1, 2)"), Ok("(abs((x - 1)) + max(1, 2))".to_string()));
+/// assert_eq!(check("50% * 0.1"), Ok("((50%) * 0.1)".to_string()));
+/// // A superscript exponent such as ⁻³ is a negative literal, written as a
+/// // negation
+/// assert_eq!(check("10⁻³"), Ok("(10 ^ (-3))".to_string()));
+/// assert_eq!(check("2^-1"), Ok("(2 ^ (-1))".to_string()));
+/// ```
+pub fn canonical_check(expression: &str, session: &Session) -> Result<String, CalcError> {
+    let tree = parse_tokens(tokenize_with(expression, session)?, session)?;
+    let canonical = to_canonical(&tree)?;
+    let reparsed = parse_tokens(tokenize_with(&canonical, session)?, session)
+        .map_err(|error| format!("'{}' does not parse: {}", canonical, error))?;
+    if normalized(&reparsed) != normalized(&tree) {
+        return Err(format!("'{}' parses differently from the input", canonical));
+    }
+
+    // `random()` differs from one evaluation to the next, so only its
+    // structure can be compared
+    let random = tree
+        .iter()
+        .any(|token| matches!(token, Token::Function(name, _) if name == "random"));
+    // Compared as text, so that NaN matches NaN
+    let value = |tree: &[Token]| format!("{:?}", evaluate_tree(tree, session));
+    if !random && value(&reparsed) != value(&tree) {
+        return Err(format!(
+            "'{}' evaluates differently from the input",
+            canonical
+        ));
+    }
+    Ok(canonical)
+}
+
+// The tree with each negative literal, as a superscript exponent gives, in
+// the form the canonical text parses to: the magnitude, then a negation
+fn normalized(tree: &[Token]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for token in tree {
+        match token {
+            Token::Integer(n) if *n < 0 => {
+                tokens.push(Token::Integer(n.wrapping_neg()));
+                tokens.push(Token::UnaryMinus);
+            }
+            Token::Number(x) if x.is_sign_negative() => {
+                tokens.push(Token::Number(-x));
+                tokens.push(Token::UnaryMinus);
+            }
+            _ => tokens.push(token.clone()),
+        }
+    }
+    tokens
+}
+
+// Write an RPN tree out as text with every operation in parentheses
+fn to_canonical(tree: &[Token]) -> Result<String, String> {
+    let invalid = || "Invalid expression format".to_string();
+    let mut stack: Vec<String> = Vec::new();
+    for token in tree {
+        let text = match token {
+            Token::Integer(n) if *n < 0 => format!("(-{})", n.unsigned_abs()),
+            Token::Integer(n) => n.to_string(),
+            // Debug keeps the decimal point, so 3.0 stays a float
+            Token::Number(x) if x.is_sign_negative() => format!("(-{:?})", -x),
+            Token::Number(x) => format!("{:?}", x),
+            Token::Imaginary(x) => format!("{:?}i", x),
+            Token::Identifier(name) => name.clone(),
+            Token::Defined(name) => format!("defined({})", name),
+            Token::Operator(op) => {
+                let b = stack.pop().ok_or_else(invalid)?;
+                let a = stack.pop().ok_or_else(invalid)?;
+                format!("({} {} {})", a, op, b)
+            }
+            Token::UnaryMinus => format!("(-{})", stack.pop().ok_or_else(invalid)?),
+            Token::Postfix(op) => format!("({}{})", stack.pop().ok_or_else(invalid)?, op),
+            Token::Function(name, count) => {
+                if stack.len() < *count {
+                    return Err(invalid());
+                }
+                let args = stack.split_off(stack.len() - count);
+                format!("{}({})", name, args.join(", "))
+            }
+            Token::LeftParenthesis | Token::RightParenthesis | Token::Comma => {
+                return Err(invalid())
+            }
+        };
+        stack.push(text);
+    }
+
+    match (stack.pop(), stack.is_empty()) {
+        (Some(text), true) => Ok(text),
+        _ => Err(invalid()),
+    }
+}
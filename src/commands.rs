@@ -0,0 +1,1086 @@
+use std::time::{Duration, Instant};
+
+use crate::calculus::derivative;
+use crate::canon::canonical_check;
+use crate::eval::{apply_operator, approx_equal, constant, evaluate_tree, mean, variance};
+use crate::explain::explain;
+use crate::format::format_result;
+use crate::functions::define_function;
+use crate::help::help;
+use crate::lexer::Token;
+use crate::session::{
+    check_statement, evaluate_statement, repeat_statement, split_call, AngleMode, DisplayMode,
+    Session, MAX_SIGNIFICANT_DIGITS,
+};
+use crate::table::table;
+use crate::units::convert;
+use crate::value::Value;
+use crate::{evaluate_expression, parse_within_limits};
+
+// Decimal places used by a bare `mode decimal`
+const DEFAULT_DECIMAL_SCALE: u32 = 10;
+
+// Results are carried between operations as f64, which only round-trips 15
+// significant digits, so finer scales would not actually be exact
+const MAX_DECIMAL_SCALE: u32 = 15;
+
+// Entries kept by `cache on`
+const DEFAULT_CACHE_CAPACITY: usize = 256;
+
+// Random points `verify` checks an identity at
+const VERIFY_SAMPLES: usize = 20;
+
+// First words of the commands `run_command` handles, which `check_input`
+// skips; keep in step with the match there
+const COMMAND_WORDS: [&str; 34] = [
+    "base",
+    "benchmark",
+    "cache",
+    "canoncheck",
+    "check",
+    "color",
+    "convert",
+    "decsep",
+    "display",
+    "explain",
+    "fracpart",
+    "freevars",
+    "groupsep",
+    "help",
+    "intpart",
+    "inv",
+    "last",
+    "max-cost",
+    "max-iterations",
+    "mode",
+    "neg",
+    "next",
+    "precision",
+    "prev",
+    "quit",
+    "reset",
+    "run",
+    "seed",
+    "sigfigs",
+    "stats",
+    "table",
+    "timeout",
+    "tolerance",
+    "verify",
+];
+
+// Upper bound for `benchmark N <expr>`
+const MAX_BENCHMARK_COUNT: usize = 10_000_000;
+
+/// Check the syntax of a line as the REPL would read it, without running
+/// anything. Commands other than `assert(...)` and `repeat` take no
+/// expression and always pass. This is what `--check-only` runs on each line.
+///
+/// ```
+/// use calc::{check_input, Session};
+///
+/// let session = Session::new();
+/// let lines = ["x = 1 + 2", "mode deg", "y = (x *"];
+/// let errors: Vec<(usize, String)> = lines
+///     .iter()
+///     .enumerate()
+///     .filter_map(|(index, line)| check_input(line, &session).err().map(|e| (index + 1, e)))
+///     .collect();
+/// assert_eq!(
+///     errors,
+///     [(3, "Expected a number or '(' but found end of input".to_string())]
+/// );
+/// ```
+pub fn check_input(input: &str, session: &Session) -> Result<(), String> {
+    let input = input.trim();
+    if let Some(("assert", args)) = split_call(input) {
+        return args
+            .iter()
+            .try_for_each(|arg| check_statement(arg, session));
+    }
+    if let Some(rest) = input.strip_prefix("repeat ") {
+        let (_, statement) = rest
+            .trim_start()
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| "Usage: repeat N <expression>".to_string())?;
+        return check_statement(statement, session);
+    }
+    match input.split_whitespace().next() {
+        Some(word) if COMMAND_WORDS.contains(&word.to_lowercase().as_str()) => Ok(()),
+        _ => check_statement(input, session),
+    }
+}
+
+/// Handle REPL commands; returns None when the input is not a command
+///
+/// ```
+/// use calc::{run_command, Session};
+///
+/// let mut session = Session::new();
+/// assert_eq!(
+///     run_command("table x^2 for x = 1 to 3", &mut session),
+///     Some(Ok("x  x^2\n1  1\n2  4\n3  9".to_string()))
+/// );
+/// ```
+///
+/// `last` echoes the previous expression exactly as it was entered:
+///
+/// ```
+/// use calc::{run_command, Session, Value};
+///
+/// let mut session = Session::new();
+/// assert_eq!(
+///     run_command("last", &mut session),
+///     Some(Err("No previous expression".to_string()))
+/// );
+/// session.record("2 *  (3+4)", Value::Int(14));
+/// assert_eq!(run_command("last", &mut session), Some(Ok("2 *  (3+4)".to_string())));
+/// ```
+///
+/// `prev` and `next` step through the history of evaluated expressions, and
+/// `run` evaluates the one they point at:
+///
+/// ```
+/// use calc::{run_command, Session, Value};
+///
+/// let mut session = Session::new();
+/// session.record("1 + 1", Value::Int(2));
+/// session.record("2 * 3", Value::Int(6));
+/// assert_eq!(run_command("prev", &mut session), Some(Ok("2 * 3".to_string())));
+/// assert_eq!(run_command("prev", &mut session), Some(Ok("1 + 1".to_string())));
+/// assert_eq!(run_command("next", &mut session), Some(Ok("2 * 3".to_string())));
+/// assert_eq!(run_command("prev", &mut session), Some(Ok("1 + 1".to_string())));
+/// assert_eq!(run_command("run", &mut session), Some(Ok("2".to_string())));
+/// assert_eq!(session.history, ["1 + 1", "2 * 3", "1 + 1"]);
+/// ```
+///
+/// `stats` summarises the numeric results in history:
+///
+/// ```
+/// use calc::{evaluate_statement, run_command, Session, Value};
+///
+/// let mut session = Session::new();
+/// for n in [2, 4, 4, 4, 5, 5, 7, 9] {
+///     session.record(&n.to_string(), Value::Int(n));
+/// }
+/// let summary = "count: 8, min: 2, max: 9, mean: 5, stddev: 2.138089935299395";
+/// assert_eq!(run_command("stats", &mut session), Some(Ok(summary.to_string())));
+///
+/// run_command("reset history", &mut session);
+/// assert!(matches!(run_command("stats", &mut session), Some(Err(_))));
+///
+/// // A list is not a single result, and is left out
+/// let data = evaluate_statement("data = 1, 2, 3", &mut session).unwrap();
+/// session.record("data = 1, 2, 3", data);
+/// session.record("5", Value::Int(5));
+/// let summary = "count: 1, min: 5, max: 5, mean: 5";
+/// assert_eq!(run_command("stats", &mut session), Some(Ok(summary.to_string())));
+/// ```
+///
+/// `freevars` lists the variables an expression still needs:
+///
+/// ```
+/// use calc::{run_command, Session};
+///
+/// let mut session = Session::new();
+/// let free = run_command("freevars x + 2*y + pi + sqrt(x)", &mut session);
+/// assert_eq!(free, Some(Ok("x, y".to_string())));
+/// ```
+///
+/// `f(x, y) = body` defines a function, with any number of parameters:
+///
+/// ```
+/// use calc::{evaluate_expression, run_command, Session, Value};
+///
+/// let mut session = Session::new();
+/// assert_eq!(
+///     run_command("hyp(a, b) = sqrt(a^2 + b^2)", &mut session),
+///     Some(Ok("Defined hyp(a, b)".to_string()))
+/// );
+/// assert_eq!(run_command("f() = 3", &mut session), Some(Ok("Defined f()".to_string())));
+/// assert_eq!(evaluate_expression("f() + hyp(3, 4)", &session), Ok(Value::Float(8.0)));
+/// assert!(evaluate_expression("f(1)", &session).is_err());
+/// ```
+///
+/// `reset vars`, `reset modes` and `reset history` each clear one part of
+/// the session, and a bare `reset` clears all of them:
+///
+/// ```
+/// use calc::{evaluate_statement, run_command, Session};
+///
+/// let mut session = Session::new();
+/// run_command("precision 2", &mut session);
+/// evaluate_statement("x = 5", &mut session).unwrap();
+/// session.record("x = 5", calc::Value::Int(5));
+///
+/// run_command("reset vars", &mut session);
+/// assert!(session.variables.is_empty());
+/// assert_eq!(session.precision, Some(2));
+/// assert_eq!(session.history, ["x = 5"]);
+///
+/// evaluate_statement("x = 5", &mut session).unwrap();
+/// run_command("reset modes", &mut session);
+/// assert_eq!(session.precision, None);
+/// assert!(session.variables.contains_key("x"));
+/// assert_eq!(session.history, ["x = 5"]);
+///
+/// run_command("reset history", &mut session);
+/// assert!(session.history.is_empty());
+/// assert!(session.variables.contains_key("x"));
+///
+/// run_command("precision 2", &mut session);
+/// run_command("reset", &mut session);
+/// assert!(session.variables.is_empty());
+/// assert_eq!(session.precision, None);
+/// ```
+///
+/// `neg` and `inv` negate the last result or take its reciprocal, and
+/// `intpart` and `fracpart` keep one part of it:
+///
+/// ```
+/// use calc::{run_command, Session, Value};
+///
+/// let mut session = Session::new();
+/// session.ans = Value::Int(4);
+/// assert_eq!(run_command("neg", &mut session), Some(Ok("-4".to_string())));
+/// assert_eq!(run_command("inv", &mut session), Some(Ok("-0.25".to_string())));
+/// assert_eq!(run_command("neg", &mut session), Some(Ok("0.25".to_string())));
+/// assert_eq!(session.ans, Value::Float(0.25));
+///
+/// session.ans = Value::Int(0);
+/// assert!(matches!(run_command("inv", &mut session), Some(Err(_))));
+///
+/// session.ans = Value::Float(0.0);
+/// assert_eq!(run_command("neg", &mut session), Some(Ok("0".to_string())));
+///
+/// session.ans = Value::Float(-7.5);
+/// assert_eq!(run_command("fracpart", &mut session), Some(Ok("-0.5".to_string())));
+/// ```
+///
+/// `derivative(expr, x)` differentiates symbolically:
+///
+/// ```
+/// use calc::{run_command, Session};
+///
+/// let mut session = Session::new();
+/// let mut derivative = |input| run_command(input, &mut session).unwrap();
+/// assert_eq!(derivative("derivative(x^2 + 3*x, x)"), Ok("2*x + 3".to_string()));
+/// assert_eq!(
+///     derivative("derivative(x*sin(x), x)"),
+///     Ok("sin(x) + x*cos(x)".to_string())
+/// );
+/// assert!(derivative("derivative(x mod 2, x)").is_err());
+/// ```
+///
+/// `convert` changes an amount from one unit of length, mass or
+/// temperature to another:
+///
+/// ```
+/// use calc::{run_command, Session};
+///
+/// let mut session = Session::new();
+/// session.precision = Some(4);
+/// assert_eq!(
+///     run_command("convert 100 km mi", &mut session),
+///     Some(Ok("62.1371 mi".to_string()))
+/// );
+/// assert_eq!(
+///     run_command("convert 37 C F", &mut session),
+///     Some(Ok("98.6 F".to_string()))
+/// );
+/// assert_eq!(
+///     run_command("convert 2 * 10 C K", &mut session),
+///     Some(Ok("293.15 K".to_string()))
+/// );
+/// assert!(matches!(run_command("convert 1 kg m", &mut session), Some(Err(_))));
+/// ```
+///
+/// `help` describes a function or operator:
+///
+/// ```
+/// use calc::{run_command, Session};
+///
+/// let mut session = Session::new();
+/// let help = run_command("help sin", &mut session).unwrap().unwrap();
+/// assert!(help.starts_with("sin(x) (1 argument)"));
+/// assert!(run_command("help ^", &mut session).unwrap().is_ok());
+/// for name in ["add", "div", "diff", "solve", "pi", "e", "min", "stddevs", "bitnot"] {
+///     assert!(run_command(&format!("help {}", name), &mut session).unwrap().is_ok());
+/// }
+/// let help = run_command("help mean", &mut session).unwrap().unwrap();
+/// assert!(help.starts_with("mean(x, ...) (1 or more arguments)"));
+/// assert_eq!(
+///     run_command("help frobnicate", &mut session),
+///     Some(Err("No help for 'frobnicate'".to_string()))
+/// );
+/// ```
+///
+/// `display` picks how results are written, optionally with a number of
+/// decimal places:
+///
+/// ```
+/// use calc::{format_result, run_command, Session, Value};
+///
+/// let mut session = Session::new();
+/// let value = Value::Float(12345.678);
+/// let mut show = |mode: &str| {
+///     run_command(&format!("display {}", mode), &mut session).unwrap()?;
+///     format_result(&value, &session)
+/// };
+/// assert_eq!(show("auto"), Ok("12345.678".to_string()));
+/// assert_eq!(show("fixed"), Ok("12345.68".to_string()));
+/// assert_eq!(show("sci"), Ok("1.234568e4".to_string()));
+/// assert_eq!(show("eng"), Ok("12.345678e3".to_string()));
+/// assert_eq!(show("currency"), Ok("$12,345.68".to_string()));
+/// assert_eq!(show("fixed 4"), Ok("12345.6780".to_string()));
+/// assert_eq!(show("sci 1"), Ok("1.2e4".to_string()));
+/// assert!(show("roman").is_err());
+/// ```
+///
+/// `explain` narrates the order of operations:
+///
+/// ```
+/// use calc::{run_command, Session};
+///
+/// let mut session = Session::new();
+/// let mut explain = |input| run_command(input, &mut session).unwrap();
+/// assert_eq!(
+///     explain("explain 2 + 3 * 4"),
+///     Ok("First, because of precedence, we multiply 3 by 4 to get 12. \
+///         Finally, we add 2 to get 14."
+///         .to_string())
+/// );
+/// assert_eq!(
+///     explain("explain 10 - 4 - 3"),
+///     Ok("First, we subtract 4 from 10 to get 6. Finally, we subtract 3 to get 3.".to_string())
+/// );
+/// let steps = explain("explain (1 + 2) * 4").unwrap();
+/// assert!(steps.starts_with("First, because of the parentheses, we add 1 and 2 to get 3."));
+/// assert_eq!(
+///     explain("explain 5"),
+///     Ok("There is nothing to work out: the result is 5.".to_string())
+/// );
+/// assert!(explain("explain 2 ^ 3").is_err());
+/// ```
+///
+/// `benchmark N <expr>` times parsing once and evaluating N times:
+///
+/// ```
+/// use calc::{run_command, Session};
+///
+/// let mut session = Session::new();
+/// let report = run_command("benchmark 100 sqrt(2) * 3", &mut session).unwrap().unwrap();
+/// assert!(report.starts_with("parse: "));
+/// assert!(report.contains(" total over 100 runs, "));
+/// assert!(report.ends_with(" average"));
+///
+/// assert!(matches!(run_command("benchmark 0 1 + 1", &mut session), Some(Err(_))));
+/// assert!(matches!(run_command("benchmark 10 1 +", &mut session), Some(Err(_))));
+/// ```
+///
+/// `verify` checks a claimed identity at random values of its variables:
+///
+/// ```
+/// use calc::{run_command, Session};
+///
+/// let mut session = Session::new();
+/// session.seed(1);
+/// let verified = run_command("verify log(a*b) == log(a) + log(b)", &mut session);
+/// assert_eq!(verified, Some(Ok("Verified at 20 random points".to_string())));
+///
+/// let refuted = run_command("verify (a+b)^2 == a^2 + b^2", &mut session);
+/// assert!(matches!(refuted, Some(Err(_))));
+/// assert_eq!(session.failed_assertions, 1);
+/// ```
+pub fn run_command(input: &str, session: &mut Session) -> Option<Result<String, String>> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+
+    if let Some(("assert", args)) = split_call(input) {
+        return Some(assert(&args, session));
+    }
+    if let Some(("derivative", args)) = split_call(input) {
+        return Some(derivative(&args, session));
+    }
+
+    let output = match words.as_slice() {
+        ["last"] => session
+            .last_input
+            .clone()
+            .ok_or_else(|| "No previous expression".to_string()),
+        ["verify", ..] => verify(input, session),
+        ["check", ..] => match input.trim_start().strip_prefix("check") {
+            Some(rest) if !rest.trim().is_empty() => check_input(rest, session).map(|_| "OK".to_string()),
+            _ => Err("Usage: check <statement>".to_string()),
+        },
+        ["canoncheck", ..] => match input.trim_start().strip_prefix("canoncheck") {
+            Some(rest) if !rest.trim().is_empty() => canonical_check(rest, session),
+            _ => Err("Usage: canoncheck <expression>".to_string()),
+        },
+        ["freevars", ..] => freevars(input, session),
+        ["help"] => help(None),
+        ["help", name] => help(Some(name)),
+        ["reset", "running"] => {
+            session.running = None;
+            Ok("Running minimum and maximum cleared".to_string())
+        }
+        ["reset", "history"] => {
+            session.reset_history();
+            Ok("History cleared".to_string())
+        }
+        ["reset", "vars"] => {
+            session.reset_variables();
+            Ok("Variables cleared".to_string())
+        }
+        ["reset", "modes"] => {
+            session.reset_modes();
+            Ok("Settings restored to defaults".to_string())
+        }
+        ["reset"] => {
+            session.reset_variables();
+            session.reset_history();
+            session.reset_modes();
+            session.running = None;
+            Ok("Session reset".to_string())
+        }
+        ["reset", ..] => Err("Usage: reset [vars|modes|history|running]".to_string()),
+        ["stats"] => stats(session),
+        ["prev"] => recall_previous(session),
+        ["next"] => recall_next(session),
+        ["run"] => run_recalled(session),
+        ["neg"] => replace_ans(session, |ans, session| {
+            // `0 - ans` rather than `ans * -1`, which leaves 0.0 as -0.0
+            apply_operator("-", &Value::Int(0), ans, session)
+        }),
+        ["intpart"] => replace_ans(session, |_, session| {
+            evaluate_expression("intpart(ans)", session)
+        }),
+        ["fracpart"] => replace_ans(session, |_, session| {
+            evaluate_expression("fracpart(ans)", session)
+        }),
+        ["inv"] => replace_ans(session, |ans, session| {
+            if !ans.is_truthy() {
+                return Err("Cannot take the reciprocal of 0".to_string());
+            }
+            apply_operator("/", &Value::Int(1), ans, session)
+        }),
+        ["mode", angle @ ("deg" | "rad")] => set_angle_mode(session, angle),
+        ["precision", places] => set_precision(session, places),
+        ["sigfigs", "off"] => {
+            session.sigfigs = None;
+            Ok("Significant figures: off".to_string())
+        }
+        ["sigfigs", digits] => match digits.parse() {
+            Ok(digits @ 1..=MAX_SIGNIFICANT_DIGITS) => {
+                session.sigfigs = Some(digits);
+                Ok(format!("Significant figures: {}", digits))
+            }
+            _ => Err(format!(
+                "Significant figures must be a whole number from 1 to {}",
+                MAX_SIGNIFICANT_DIGITS
+            )),
+        },
+        ["base", base] => set_base(session, base),
+        ["color", setting] => set_color(session, setting),
+        ["tolerance", tolerance] => set_tolerance(session, tolerance),
+        ["seed", seed] => set_seed(session, seed),
+        ["max-cost", limit] => set_max_cost(session, limit),
+        ["max-iterations", limit] => set_max_iterations(session, limit),
+        ["timeout", timeout] => set_timeout(session, timeout),
+        ["groupsep", separator] => set_group_separator(session, separator),
+        ["decsep", separator] => set_decimal_separator(session, separator),
+        ["benchmark", ..] => benchmark(input, session),
+        ["explain", ..] => explain(input, session),
+        ["table", ..] => table(input, session),
+        ["convert", ..] => convert(input, session),
+        ["display"] => Ok(format!("Display: {}", display_name(session.display_mode))),
+        ["display", mode] => set_display(session, mode, None),
+        ["display", mode, places] => set_display(session, mode, Some(places)),
+        ["mode", "simple"] => {
+            session.simple_mode = true;
+            Ok("Precedence: left to right".to_string())
+        }
+        ["mode", "bodmas"] => {
+            session.simple_mode = false;
+            Ok("Precedence: BODMAS".to_string())
+        }
+        ["mode", "eng"] => {
+            session.display_mode = DisplayMode::Engineering;
+            Ok("Display: engineering (SI suffixes enabled)".to_string())
+        }
+        ["mode", "currency"] => {
+            session.display_mode = DisplayMode::Currency;
+            Ok(format!("Display: currency ({})", session.currency_symbol))
+        }
+        ["mode", "auto"] => {
+            session.display_mode = DisplayMode::Auto;
+            Ok("Display: auto".to_string())
+        }
+        ["cache", "on"] => {
+            session.enable_cache(DEFAULT_CACHE_CAPACITY);
+            Ok("Result cache: on".to_string())
+        }
+        ["cache", "off"] => {
+            session.disable_cache();
+            Ok("Result cache: off".to_string())
+        }
+        ["mode", "decimal"] => set_decimal_scale(session, DEFAULT_DECIMAL_SCALE),
+        ["mode", "decimal", scale] => match scale.parse() {
+            Ok(scale) if scale <= MAX_DECIMAL_SCALE => set_decimal_scale(session, scale),
+            _ => Err(format!(
+                "Decimal scale must be a whole number from 0 to {}",
+                MAX_DECIMAL_SCALE
+            )),
+        },
+        ["mode", "roundans", "off"] => {
+            session.round_ans = None;
+            Ok("Stored results: full precision".to_string())
+        }
+        ["mode", "roundans", digits] => match digits.parse() {
+            Ok(digits @ 1..=MAX_SIGNIFICANT_DIGITS) => {
+                session.round_ans = Some(digits);
+                Ok(format!("Stored results: {} significant digits", digits))
+            }
+            _ => Err(format!(
+                "Significant digits must be a whole number from 1 to {}",
+                MAX_SIGNIFICANT_DIGITS
+            )),
+        },
+        ["mode", "float"] => {
+            session.decimal_scale = None;
+            Ok("Arithmetic: floating point".to_string())
+        }
+        ["mode", "frac"] => {
+            session.frac_mode = true;
+            Ok("Arithmetic: exact fractions".to_string())
+        }
+        ["mode", "nofrac"] => {
+            session.frac_mode = false;
+            Ok("Arithmetic: fractions as decimals".to_string())
+        }
+        ["mode", "strict"] => {
+            session.strict_mode = true;
+            Ok("Non-finite results: error".to_string())
+        }
+        ["mode", "permissive"] => {
+            session.strict_mode = false;
+            Ok("Non-finite results: allowed".to_string())
+        }
+        ["mode", "warn"] => {
+            session.warn_mode = true;
+            Ok("Warnings: on".to_string())
+        }
+        ["mode", "nowarn"] => {
+            session.warn_mode = false;
+            Ok("Warnings: off".to_string())
+        }
+        ["mode", "bool"] => {
+            session.bool_mode = true;
+            Ok("Booleans: true/false".to_string())
+        }
+        ["mode", "nobool"] => {
+            session.bool_mode = false;
+            Ok("Booleans: 1/0".to_string())
+        }
+        ["mode", "strictbase"] => {
+            session.strict_base = true;
+            Ok("Non-integers in other bases: error".to_string())
+        }
+        ["mode", "nostrictbase"] => {
+            session.strict_base = false;
+            Ok("Non-integers in other bases: shown in decimal".to_string())
+        }
+        ["mode", "twoscomplement"] => {
+            session.twos_complement = true;
+            Ok("Negative numbers in other bases: two's complement".to_string())
+        }
+        ["mode", "signed"] => {
+            session.twos_complement = false;
+            Ok("Negative numbers in other bases: signed".to_string())
+        }
+        ["mode", "strict-paren"] => {
+            session.strict_paren = true;
+            Ok("Adjacent values: missing '*' reported".to_string())
+        }
+        ["mode", "nostrict-paren"] => {
+            session.strict_paren = false;
+            Ok("Adjacent values: plain syntax errors".to_string())
+        }
+        ["mode", "angleunit"] => {
+            session.angle_unit = true;
+            Ok("Angles from inverse trig: marked with °".to_string())
+        }
+        ["mode", "noangleunit"] => {
+            session.angle_unit = false;
+            Ok("Angles from inverse trig: unmarked".to_string())
+        }
+        ["mode", "verbose"] => {
+            session.verbose_mode = true;
+            Ok("Result types: shown".to_string())
+        }
+        ["mode", "terse"] => {
+            session.verbose_mode = false;
+            Ok("Result types: hidden".to_string())
+        }
+        ["mode", "complex"] => {
+            session.complex_mode = true;
+            Ok("Numbers: complex".to_string())
+        }
+        ["mode", "real"] => {
+            session.complex_mode = false;
+            Ok("Numbers: real".to_string())
+        }
+        ["mode", "finance"] => {
+            session.finance_mode = true;
+            Ok("Percentages: relative to the left operand of + and -".to_string())
+        }
+        ["mode", "nofinance"] => {
+            session.finance_mode = false;
+            Ok("Percentages: hundredths".to_string())
+        }
+        ["mode", "time"] => {
+            session.time_mode = true;
+            Ok("Times: H:M and H:M:S read as minutes".to_string())
+        }
+        ["mode", "notime"] => {
+            session.time_mode = false;
+            Ok("Times: off".to_string())
+        }
+        ["mode", "autoclose"] => {
+            session.auto_close = true;
+            Ok("Unclosed parentheses: closed automatically".to_string())
+        }
+        ["mode", "noautoclose"] => {
+            session.auto_close = false;
+            Ok("Unclosed parentheses: error".to_string())
+        }
+        ["mode", ..] => Err(
+            "Usage: mode deg|rad|simple|bodmas|eng|currency|auto|decimal [N]|float|roundans N|roundans off|frac|nofrac|strict|permissive|warn|nowarn|bool|nobool|strictbase|nostrictbase|twoscomplement|signed|strict-paren|nostrict-paren|angleunit|noangleunit|verbose|terse|complex|real|finance|nofinance|time|notime|autoclose|noautoclose"
+                .to_string(),
+        ),
+        _ => return define_function(input, session),
+    };
+
+    Some(output)
+}
+
+// `stats`: summarise the numeric results in history. The standard deviation
+// is the sample one, as for `stddev`, so it needs at least two values.
+fn stats(session: &Session) -> Result<String, String> {
+    let values: Vec<f64> = session
+        .results
+        .iter()
+        .filter(|value| !matches!(value, Value::Bool(_) | Value::Complex(_) | Value::List(_)))
+        .map(Value::as_f64)
+        .collect();
+    if values.is_empty() {
+        return Err("No numeric results in history".to_string());
+    }
+
+    let show = |x: f64| format_result(&Value::Float(x), session);
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let mut summary = format!(
+        "count: {}, min: {}, max: {}, mean: {}",
+        values.len(),
+        show(min)?,
+        show(max)?,
+        show(mean(&values))?
+    );
+    if values.len() > 1 {
+        summary += &format!(", stddev: {}", show(variance(&values, 1).sqrt())?);
+    }
+    Ok(summary)
+}
+
+// `prev`: step back to the next older expression, starting from the newest
+fn recall_previous(session: &mut Session) -> Result<String, String> {
+    let index = match session.recall {
+        None if session.history.is_empty() => return Err("No previous expression".to_string()),
+        None => session.history.len() - 1,
+        Some(0) => return Err("Already at the oldest expression".to_string()),
+        Some(index) => index - 1,
+    };
+    session.recall = Some(index);
+    Ok(session.history[index].clone())
+}
+
+// `next`: step forward to the next newer expression
+fn recall_next(session: &mut Session) -> Result<String, String> {
+    match session.recall {
+        Some(index) if index + 1 < session.history.len() => {
+            session.recall = Some(index + 1);
+            Ok(session.history[index + 1].clone())
+        }
+        _ => Err("Already at the newest expression".to_string()),
+    }
+}
+
+// `run`: evaluate the expression picked by `prev`/`next`, which then becomes
+// the newest entry in history like any other evaluation
+fn run_recalled(session: &mut Session) -> Result<String, String> {
+    let input = session
+        .recall
+        .map(|index| session.history[index].clone())
+        .ok_or_else(|| "No expression selected (use prev first)".to_string())?;
+    let value = match input.strip_prefix("repeat ") {
+        Some(rest) => repeat_statement(rest, session)?,
+        None => evaluate_statement(&input, session)?,
+    };
+    let result = format_result(&value, session)?;
+    session.record(&input, value);
+    Ok(result)
+}
+
+// `neg`, `inv`, `intpart` and `fracpart`: change the last result in
+// place, as `ans` and `_`
+fn replace_ans(
+    session: &mut Session,
+    change: fn(&Value, &Session) -> Result<Value, String>,
+) -> Result<String, String> {
+    let value = change(&session.ans, session)?;
+    let result = format_result(&value, session)?;
+    session.ans = value;
+    Ok(result)
+}
+
+// `assert(expr, expected)`: check that two expressions agree within the
+// session's tolerance
+fn assert(args: &[&str], session: &mut Session) -> Result<String, String> {
+    let [expression, expected] = args else {
+        return Err("Usage: assert(expression, expected)".to_string());
+    };
+
+    let actual = evaluate_expression(expression, session)?.as_f64();
+    let wanted = evaluate_expression(expected, session)?.as_f64();
+    if approx_equal(actual, wanted, session.tolerance) {
+        return Ok("OK".to_string());
+    }
+
+    session.failed_assertions += 1;
+    Err(format!(
+        "Assertion failed: {} is {}, expected {}",
+        expression.trim(),
+        actual,
+        wanted
+    ))
+}
+
+// `freevars <expr>`: list the names an expression reads that have no value
+fn freevars(input: &str, session: &Session) -> Result<String, String> {
+    let expression = input
+        .trim_start()
+        .strip_prefix("freevars")
+        .filter(|rest| !rest.trim().is_empty())
+        .ok_or_else(|| "Usage: freevars <expression>".to_string())?;
+    let mut free = Vec::new();
+    free_variables(expression, session, &mut free)?;
+    if free.is_empty() {
+        return Ok("No free variables".to_string());
+    }
+    Ok(free.join(", "))
+}
+
+// Add the variables an expression reads that aren't constants, session
+// variables or the likes of `ans` to `free`, in order of first use. Calls
+// and the name in `defined(name)` don't count.
+fn free_variables(
+    expression: &str,
+    session: &Session,
+    free: &mut Vec<String>,
+) -> Result<(), String> {
+    let tree = parse_within_limits(expression, session)?;
+    for token in tree {
+        let Token::Identifier(name) = token else {
+            continue;
+        };
+        let imaginary = session.complex_mode && name == "i";
+        if !imaginary
+            && !matches!(name.as_str(), "ans" | "_" | "runmin" | "runmax")
+            && constant(&name).is_none()
+            && !session.variables.contains_key(&name)
+            && !free.contains(&name)
+        {
+            free.push(name);
+        }
+    }
+    Ok(())
+}
+
+// `verify <lhs> == <rhs>`: check a claimed identity by giving every free
+// variable a random positive value and comparing the two sides, at several
+// sets of values. Like `assert`, a mismatch counts as a failed assertion.
+fn verify(input: &str, session: &mut Session) -> Result<String, String> {
+    let usage = || "Usage: verify <expression> == <expression>".to_string();
+    let rest = input
+        .trim_start()
+        .strip_prefix("verify")
+        .ok_or_else(usage)?;
+    let (lhs, rhs) = rest.split_once("==").ok_or_else(usage)?;
+
+    let mut free = Vec::new();
+    free_variables(lhs, session, &mut free)?;
+    free_variables(rhs, session, &mut free)?;
+
+    let mut outcome = Ok(format!("Verified at {} random points", VERIFY_SAMPLES));
+    for _ in 0..VERIFY_SAMPLES {
+        // Positive and away from zero, so logarithms and roots are defined
+        for name in &free {
+            let value = 0.5 + 9.5 * session.next_random();
+            session.variables.insert(name.clone(), Value::Float(value));
+        }
+        let sides = evaluate_expression(lhs, session)
+            .and_then(|left| Ok((left.as_f64(), evaluate_expression(rhs, session)?.as_f64())));
+        let (left, right) = match sides {
+            Ok(sides) => sides,
+            Err(error) => {
+                outcome = Err(error);
+                break;
+            }
+        };
+        if !approx_equal(left, right, session.tolerance) {
+            let point: Vec<String> = free
+                .iter()
+                .map(|name| format!("{} = {}", name, session.variables[name].as_f64()))
+                .collect();
+            session.failed_assertions += 1;
+            outcome = Err(format!(
+                "Identity fails at {}: {} vs {}",
+                point.join(", "),
+                left,
+                right
+            ));
+            break;
+        }
+    }
+
+    for name in &free {
+        session.variables.remove(name);
+    }
+    outcome
+}
+
+// `benchmark N <expr>`: parse once, then evaluate N times, timing each stage
+fn benchmark(input: &str, session: &Session) -> Result<String, String> {
+    let usage = || "Usage: benchmark N <expression>".to_string();
+    let rest = input
+        .trim_start()
+        .strip_prefix("benchmark")
+        .ok_or_else(usage)?;
+    let (count, expression) = rest
+        .trim_start()
+        .split_once(char::is_whitespace)
+        .ok_or_else(usage)?;
+    let count: usize = count
+        .parse()
+        .map_err(|_| format!("Invalid benchmark count '{}'", count))?;
+    if count == 0 || count > MAX_BENCHMARK_COUNT {
+        return Err(format!(
+            "Benchmark count must be from 1 to {}",
+            MAX_BENCHMARK_COUNT
+        ));
+    }
+
+    let start = Instant::now();
+    let tree = parse_within_limits(expression.trim(), session)?;
+    let parse_time = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..count {
+        evaluate_tree(&tree, session)?;
+    }
+    let eval_time = start.elapsed();
+    // Warnings from N identical runs aren't worth reporting
+    session.take_notes();
+
+    Ok(format!(
+        "parse: {:?}, evaluate: {:?} total over {} runs, {:?} average",
+        parse_time,
+        eval_time,
+        count,
+        eval_time / count as u32
+    ))
+}
+
+fn set_decimal_scale(session: &mut Session, scale: u32) -> Result<String, String> {
+    session.decimal_scale = Some(scale);
+    Ok(format!("Arithmetic: decimal, {} places", scale))
+}
+
+pub(crate) fn set_angle_mode(session: &mut Session, mode: &str) -> Result<String, String> {
+    match mode {
+        "deg" => {
+            session.angle_mode = AngleMode::Degrees;
+            Ok("Angle mode: degrees".to_string())
+        }
+        "rad" => {
+            session.angle_mode = AngleMode::Radians;
+            Ok("Angle mode: radians".to_string())
+        }
+        _ => Err(format!(
+            "Unknown angle mode '{}' (expected deg or rad)",
+            mode
+        )),
+    }
+}
+
+// `display auto|fixed|sci|eng|currency [N]`: the notation for results,
+// optionally with the number of decimal places
+fn set_display(session: &mut Session, mode: &str, places: Option<&str>) -> Result<String, String> {
+    let mode = match mode {
+        "auto" => DisplayMode::Auto,
+        "fixed" => DisplayMode::Fixed,
+        "sci" => DisplayMode::Scientific,
+        "eng" => DisplayMode::Engineering,
+        "currency" => DisplayMode::Currency,
+        _ => return Err("Usage: display auto|fixed|sci|eng|currency [N]".to_string()),
+    };
+    if let Some(places) = places {
+        set_precision(session, places)?;
+    }
+
+    session.display_mode = mode;
+    Ok(match session.precision {
+        // Currency always shows cents
+        Some(places) if mode != DisplayMode::Currency => {
+            format!("Display: {}, {} decimal places", display_name(mode), places)
+        }
+        _ => format!("Display: {}", display_name(mode)),
+    })
+}
+
+fn display_name(mode: DisplayMode) -> &'static str {
+    match mode {
+        DisplayMode::Auto => "auto",
+        DisplayMode::Fixed => "fixed",
+        DisplayMode::Scientific => "scientific",
+        DisplayMode::Engineering => "engineering",
+        DisplayMode::Currency => "currency",
+    }
+}
+
+pub(crate) fn set_precision(session: &mut Session, places: &str) -> Result<String, String> {
+    if places == "auto" {
+        session.precision = None;
+        session.shortest = true;
+        return Ok("Precision: shortest exact form".to_string());
+    }
+    let places = places
+        .parse()
+        .map_err(|_| format!("Invalid precision '{}'", places))?;
+    session.precision = Some(places);
+    session.shortest = false;
+    Ok(format!("Precision: {} decimal places", places))
+}
+
+pub(crate) fn set_max_cost(session: &mut Session, limit: &str) -> Result<String, String> {
+    match limit.parse() {
+        Ok(limit) if limit > 0 => {
+            session.max_cost = limit;
+            Ok(format!("Expression cost limit: {}", limit))
+        }
+        _ => Err(format!("Invalid cost limit '{}'", limit)),
+    }
+}
+
+pub(crate) fn set_max_iterations(session: &mut Session, limit: &str) -> Result<String, String> {
+    match limit.parse() {
+        Ok(limit) if limit > 0 => {
+            session.max_iterations = limit;
+            Ok(format!("Function call limit: {}", limit))
+        }
+        _ => Err(format!("Invalid iteration limit '{}'", limit)),
+    }
+}
+
+pub(crate) fn set_group_separator(
+    session: &mut Session,
+    separator: &str,
+) -> Result<String, String> {
+    let separator = parse_separator(separator)?;
+    session.group_separator = separator;
+    Ok(format!("Group separator: '{}'", separator))
+}
+
+pub(crate) fn set_decimal_separator(
+    session: &mut Session,
+    separator: &str,
+) -> Result<String, String> {
+    let separator = parse_separator(separator)?;
+    session.decimal_separator = separator;
+    Ok(format!("Decimal separator: '{}'", separator))
+}
+
+// A single character that can't be mistaken for a digit or a sign. The two
+// separators may match for a moment while swapping them over.
+fn parse_separator(separator: &str) -> Result<char, String> {
+    let mut chars = separator.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_ascii_alphanumeric() && !matches!(c, '-' | '+') => Ok(c),
+        _ => Err(format!("Invalid separator '{}'", separator)),
+    }
+}
+
+// `timeout N` in milliseconds, or `timeout off`
+pub(crate) fn set_timeout(session: &mut Session, timeout: &str) -> Result<String, String> {
+    if timeout == "off" {
+        session.timeout = None;
+        return Ok("Timeout: off".to_string());
+    }
+    match timeout.parse() {
+        Ok(millis) if millis > 0 => {
+            session.timeout = Some(Duration::from_millis(millis));
+            Ok(format!("Timeout: {} ms", millis))
+        }
+        _ => Err(format!("Invalid timeout '{}'", timeout)),
+    }
+}
+
+pub(crate) fn set_tolerance(session: &mut Session, tolerance: &str) -> Result<String, String> {
+    match tolerance.parse::<f64>() {
+        Ok(tolerance) if tolerance >= 0.0 && tolerance.is_finite() => {
+            session.tolerance = tolerance;
+            Ok(format!("Tolerance: {}", tolerance))
+        }
+        _ => Err(format!("Invalid tolerance '{}'", tolerance)),
+    }
+}
+
+pub(crate) fn set_seed(session: &mut Session, seed: &str) -> Result<String, String> {
+    match seed.parse::<u64>() {
+        Ok(seed) => {
+            session.seed(seed);
+            Ok(format!("Seed: {}", seed))
+        }
+        Err(_) => Err(format!("Invalid seed '{}'", seed)),
+    }
+}
+
+pub(crate) fn set_base(session: &mut Session, base: &str) -> Result<String, String> {
+    match base.parse() {
+        Ok(base @ (2 | 8 | 10 | 16)) => {
+            session.output_base = base;
+            Ok(format!("Output base: {}", base))
+        }
+        _ => Err(format!(
+            "Unsupported base '{}' (expected 2, 8, 10 or 16)",
+            base
+        )),
+    }
+}
+
+pub(crate) fn set_color(session: &mut Session, setting: &str) -> Result<String, String> {
+    session.color = match setting {
+        "on" | "true" => true,
+        "off" | "false" => false,
+        _ => {
+            return Err(format!(
+                "Invalid color setting '{}' (expected on or off)",
+                setting
+            ))
+        }
+    };
+    Ok(format!(
+        "Color: {}",
+        if session.color { "on" } else { "off" }
+    ))
+}
@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+
+use crate::eval::evaluate_scoped;
+use crate::lexer::{tokenize_with, Token};
+use crate::parser::parse_tokens;
+use crate::session::Session;
+use crate::value::Value;
+use crate::CalcError;
+
+/// An expression parsed once, to be evaluated many times with different
+/// variable values. Built by [`compile`].
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// let distance = calc::compile("sqrt(x^2 + y^2)").unwrap();
+/// let mut vars = HashMap::new();
+/// for (x, y, expected) in [(3.0, 4.0, 5.0), (5.0, 12.0, 13.0), (8.0, 15.0, 17.0)] {
+///     vars.insert("x".to_string(), x);
+///     vars.insert("y".to_string(), y);
+///     assert_eq!(distance.eval(&vars), Ok(expected));
+/// }
+///
+/// vars.remove("y");
+/// assert!(distance.eval(&vars).is_err());
+/// ```
+pub struct CompiledExpr {
+    tree: Vec<Token>,
+    // Default settings, kept so each evaluation doesn't build a new session
+    session: Session,
+}
+
+impl CompiledExpr {
+    /// Evaluate with the given variables, which may be different every time
+    pub fn eval(&self, vars: &HashMap<String, f64>) -> Result<f64, CalcError> {
+        let locals: HashMap<String, Value> = vars
+            .iter()
+            .map(|(name, value)| (name.clone(), Value::Float(*value)))
+            .collect();
+        self.session.reset_calls();
+        evaluate_scoped(&self.tree, &self.session, &locals).map(|value| value.as_f64())
+    }
+}
+
+/// Parse an expression once for repeated evaluation with
+/// [`CompiledExpr::eval`], using default settings
+pub fn compile(expression: &str) -> Result<CompiledExpr, CalcError> {
+    let session = Session::new();
+    let tree = parse_tokens(tokenize_with(expression, &session)?, &session)?;
+    Ok(CompiledExpr { tree, session })
+}
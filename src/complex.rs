@@ -0,0 +1,135 @@
+use crate::value::Value;
+
+/// A complex number, produced in `mode complex` by the imaginary unit `i`
+/// and literals such as `4i`
+///
+/// ```
+/// use calc::{evaluate_expression, format_result, Session, Value};
+///
+/// let mut session = Session::new();
+/// session.complex_mode = true;
+/// let product = evaluate_expression("(3+4i)*(1-2i)", &session).unwrap();
+/// assert_eq!(format_result(&product, &session), Ok("11-2i".to_string()));
+/// assert_eq!(evaluate_expression("abs(3+4i)", &session), Ok(Value::Float(5.0)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    fn conj(self) -> Complex {
+        Complex {
+            re: self.re,
+            im: -self.im,
+        }
+    }
+
+    fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    // Principal square root, with a non-negative real part
+    fn sqrt(self) -> Complex {
+        let modulus = self.abs();
+        let re = ((modulus + self.re) / 2.0).sqrt();
+        let im = ((modulus - self.re) / 2.0).sqrt();
+        Complex {
+            re,
+            im: if self.im < 0.0 { -im } else { im },
+        }
+    }
+}
+
+// A result with no imaginary part goes back to being an ordinary number, so
+// `(3+4i)*(3-4i)` is plain 25
+pub(crate) fn to_value(z: Complex) -> Value {
+    if z.im == 0.0 {
+        Value::Float(z.re)
+    } else {
+        Value::Complex(z)
+    }
+}
+
+pub(crate) fn from_value(value: &Value) -> Complex {
+    match value {
+        Value::Complex(z) => *z,
+        _ => Complex {
+            re: value.as_f64(),
+            im: 0.0,
+        },
+    }
+}
+
+// Operators over complex operands. Only arithmetic, whole powers and
+// equality are defined; complex numbers have no ordering.
+pub(crate) fn apply(op: &str, a: Complex, b: Complex) -> Result<Value, String> {
+    let result = match op {
+        "+" => Complex {
+            re: a.re + b.re,
+            im: a.im + b.im,
+        },
+        "-" => Complex {
+            re: a.re - b.re,
+            im: a.im - b.im,
+        },
+        "*" => multiply(a, b),
+        "/" => {
+            let denominator = b.re * b.re + b.im * b.im;
+            if denominator == 0.0 {
+                return Err("Division by zero".to_string());
+            }
+            Complex {
+                re: (a.re * b.re + a.im * b.im) / denominator,
+                im: (a.im * b.re - a.re * b.im) / denominator,
+            }
+        }
+        // Whole powers only, by repeated squaring
+        "^" if b.im == 0.0 && b.re.fract() == 0.0 && b.re.abs() <= i32::MAX as f64 => {
+            let (mut base, mut power) = (a, b.re.abs() as u32);
+            let mut result = Complex { re: 1.0, im: 0.0 };
+            while power > 0 {
+                if power & 1 == 1 {
+                    result = multiply(result, base);
+                }
+                base = multiply(base, base);
+                power >>= 1;
+            }
+            if b.re < 0.0 {
+                return apply("/", Complex { re: 1.0, im: 0.0 }, result);
+            }
+            result
+        }
+        "==" => return Ok(Value::Bool(a == b)),
+        "!=" => return Ok(Value::Bool(a != b)),
+        _ => {
+            return Err(format!(
+                "Operator '{}' is not supported for complex numbers",
+                op
+            ))
+        }
+    };
+    Ok(to_value(result))
+}
+
+fn multiply(a: Complex, b: Complex) -> Complex {
+    Complex {
+        re: a.re * b.re - a.im * b.im,
+        im: a.re * b.im + a.im * b.re,
+    }
+}
+
+// Functions that accept complex arguments, or None if `name` isn't one
+pub(crate) fn call(name: &str, args: &[Value]) -> Option<Result<Value, String>> {
+    let function: fn(Complex) -> Value = match name {
+        "abs" => |z| Value::Float(z.abs()),
+        "conj" => |z| to_value(z.conj()),
+        "sqrt" => |z| to_value(z.sqrt()),
+        _ => return None,
+    };
+    Some(match args {
+        [z] => Ok(function(from_value(z))),
+        _ => Err(format!("Function '{}' expects 1 argument", name)),
+    })
+}
@@ -0,0 +1,67 @@
+use crate::commands::{
+    set_angle_mode, set_base, set_color, set_decimal_separator, set_group_separator, set_max_cost,
+    set_max_iterations, set_precision, set_seed, set_timeout, set_tolerance,
+};
+use crate::session::Session;
+
+/// Apply `key=value` settings, one per line, to the session. Blank lines and
+/// lines starting with `#` are skipped. Problems with individual lines are
+/// returned as warnings rather than aborting, so the remaining settings still
+/// take effect.
+///
+/// Recognised keys are `precision`, `angle` (`deg`/`rad`), `base`, `color`,
+/// `max-cost`, `max-iterations`, `timeout` (milliseconds or `off`), `tolerance`, `seed`,
+/// `groupsep` and `decsep`.
+///
+/// ```
+/// use calc::{apply_config, AngleMode, Session};
+///
+/// let mut session = Session::new();
+/// let config = "# defaults\nprecision = 4\nangle=deg\n\nsize\nbase = 7\nprecision = 2\n";
+/// let warnings = apply_config(config, &mut session);
+/// assert_eq!(warnings.len(), 2);
+/// assert!(warnings[0].starts_with("line 5: "));
+/// assert!(warnings[1].starts_with("line 6: "));
+/// // Later lines win
+/// assert_eq!(session.precision, Some(2));
+/// assert_eq!(session.angle_mode, AngleMode::Degrees);
+/// ```
+pub fn apply_config(text: &str, session: &mut Session) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            warnings.push(format!("line {}: expected key=value", index + 1));
+            continue;
+        };
+        if let Err(error) = apply_setting(key.trim(), value.trim(), session) {
+            warnings.push(format!("line {}: {}", index + 1, error));
+        }
+    }
+
+    warnings
+}
+
+/// Apply a single configuration setting, as found in a config file or given
+/// as a command-line flag
+pub fn apply_setting(key: &str, value: &str, session: &mut Session) -> Result<String, String> {
+    match key {
+        "precision" => set_precision(session, value),
+        "angle" => set_angle_mode(session, value),
+        "base" => set_base(session, value),
+        "color" => set_color(session, value),
+        "max-cost" => set_max_cost(session, value),
+        "max-iterations" => set_max_iterations(session, value),
+        "timeout" => set_timeout(session, value),
+        "tolerance" => set_tolerance(session, value),
+        "seed" => set_seed(session, value),
+        "groupsep" => set_group_separator(session, value),
+        "decsep" => set_decimal_separator(session, value),
+        _ => Err(format!("unknown key '{}'", key)),
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+
+// Fixed-point base-10 number for `mode decimal`: mantissa / 10^scale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    // Convert through the float's shortest round-trip text, so a literal such
+    // as 0.1 becomes exactly 1/10 rather than its binary approximation
+    pub(crate) fn from_f64(value: f64) -> Option<Decimal> {
+        if !value.is_finite() {
+            return None;
+        }
+
+        let text = value.to_string();
+        let (digits, scale) = match text.split_once('.') {
+            Some((whole, fraction)) => (format!("{}{}", whole, fraction), fraction.len() as u32),
+            None => (text, 0),
+        };
+
+        Some(Decimal {
+            mantissa: digits.parse().ok()?,
+            scale,
+        })
+    }
+
+    // The nearest float; exact for anything with at most 15 significant digits
+    pub(crate) fn to_f64(self) -> f64 {
+        self.to_string().parse().unwrap()
+    }
+
+    // Raise the scale without changing the value
+    pub(crate) fn rescale(self, scale: u32) -> Option<Decimal> {
+        let factor = 10i128.checked_pow(scale.checked_sub(self.scale)?)?;
+        Some(Decimal {
+            mantissa: self.mantissa.checked_mul(factor)?,
+            scale,
+        })
+    }
+
+    // Lower the scale to at most `scale`, rounding half away from zero
+    pub(crate) fn round_to(self, scale: u32) -> Option<Decimal> {
+        if self.scale <= scale {
+            return Some(self);
+        }
+
+        let divisor = 10i128.checked_pow(self.scale - scale)?;
+        Some(Decimal {
+            mantissa: divide_rounded(self.mantissa, divisor),
+            scale,
+        })
+    }
+
+    fn aligned(self, other: Decimal) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(other.scale);
+        Some((
+            self.rescale(scale)?.mantissa,
+            other.rescale(scale)?.mantissa,
+            scale,
+        ))
+    }
+
+    fn add(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        Some(Decimal {
+            mantissa: a.checked_add(b)?,
+            scale,
+        })
+    }
+
+    fn sub(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        Some(Decimal {
+            mantissa: a.checked_sub(b)?,
+            scale,
+        })
+    }
+
+    fn mul(self, other: Decimal) -> Option<Decimal> {
+        Some(Decimal {
+            mantissa: self.mantissa.checked_mul(other.mantissa)?,
+            scale: self.scale + other.scale,
+        })
+    }
+
+    // Quotient rounded to `scale` decimal places
+    fn div(self, other: Decimal, scale: u32) -> Option<Decimal> {
+        let (a, b, _) = self.aligned(other)?;
+        if b == 0 {
+            return None;
+        }
+        let numerator = a.checked_mul(10i128.checked_pow(scale)?)?;
+        Some(Decimal {
+            mantissa: divide_rounded(numerator, b),
+            scale,
+        })
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let digits = format!(
+            "{:0>width$}",
+            self.mantissa.unsigned_abs(),
+            width = self.scale as usize + 1
+        );
+        let (whole, fraction) = digits.split_at(digits.len() - self.scale as usize);
+        if fraction.is_empty() {
+            write!(f, "{}{}", sign, whole)
+        } else {
+            write!(f, "{}{}.{}", sign, whole, fraction)
+        }
+    }
+}
+
+// Integer division rounding half away from zero
+fn divide_rounded(numerator: i128, divisor: i128) -> i128 {
+    let quotient = numerator / divisor;
+    let remainder = numerator % divisor;
+    if remainder.unsigned_abs() * 2 >= divisor.unsigned_abs() {
+        quotient + numerator.signum() * divisor.signum()
+    } else {
+        quotient
+    }
+}
+
+// Apply `+ - * /` exactly in base 10, rounding to `scale` decimal places.
+// Returns None when the operator isn't one of those or an operand doesn't fit
+// in the fixed-point range, and the caller falls back to float arithmetic.
+pub(crate) fn apply(op: &str, operand1: f64, operand2: f64, scale: u32) -> Option<f64> {
+    let a = Decimal::from_f64(operand1)?;
+    let b = Decimal::from_f64(operand2)?;
+    let result = match op {
+        "+" => a.add(b)?,
+        "-" => a.sub(b)?,
+        "*" => a.mul(b)?,
+        "/" => a.div(b, scale)?,
+        _ => return None,
+    };
+    Some(result.round_to(scale)?.to_f64())
+}
@@ -0,0 +1,729 @@
+use std::collections::HashMap;
+
+use crate::complex::{self, Complex};
+use crate::decimal;
+use crate::functions::call_user_function;
+use crate::help::check_call;
+use crate::lexer::Token;
+use crate::ratio;
+use crate::session::{AngleMode, Session};
+use crate::value::Value;
+
+// Evaluate the syntax tree. The tree is in RPN, so evaluation is a single
+// pass over a value stack with no recursion: nesting depth and chain length
+// are bounded only by memory. The exception is user functions, whose calls
+// nest and are limited by the session's call budget.
+pub(crate) fn evaluate_tree(tokens: &[Token], session: &Session) -> Result<Value, String> {
+    session.reset_calls();
+    evaluate_scoped(tokens, session, &HashMap::new())
+}
+
+// Extra cost of a function call over any other token
+const FUNCTION_CALL_COST: usize = 10;
+
+// Tokens evaluated between checks of the session's timeout
+const DEADLINE_CHECK_INTERVAL: usize = 256;
+
+// Evaluate with local bindings, e.g. a user function's parameters, which
+// take priority over everything else
+pub(crate) fn evaluate_scoped(
+    tokens: &[Token],
+    session: &Session,
+    locals: &HashMap<String, Value>,
+) -> Result<Value, String> {
+    let mut stack: Vec<Value> = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        // Nested user function calls start their own count, so deep recursion
+        // is checked at every call
+        if index % DEADLINE_CHECK_INTERVAL == 0 {
+            session.check_deadline()?;
+        }
+        match token {
+            Token::Identifier(name) if locals.contains_key(name) => {
+                stack.push(locals[name].clone())
+            }
+            Token::Defined(name) => stack.push(Value::Bool(
+                locals.contains_key(name)
+                    || session.variables.contains_key(name)
+                    || constant(name).is_some(),
+            )),
+            Token::Integer(num) => stack.push(Value::Int(*num)),
+            Token::Number(num) => stack.push(Value::Float(*num)),
+            Token::Imaginary(num) => stack.push(complex::to_value(Complex { re: 0.0, im: *num })),
+            // `_` is the Python-style spelling of `ans`
+            Token::Identifier(name) if name == "ans" || name == "_" => {
+                stack.push(session.ans.clone())
+            }
+            Token::Identifier(name) if name == "runmin" || name == "runmax" => {
+                let (min, max) = session
+                    .running
+                    .clone()
+                    .ok_or_else(|| format!("No results yet for '{}'", name))?;
+                stack.push(if name == "runmin" { min } else { max });
+            }
+            Token::Identifier(name) if constant(name).is_some() => {
+                stack.push(constant(name).unwrap())
+            }
+            Token::Identifier(name) if name == "i" && session.complex_mode => {
+                stack.push(Value::Complex(Complex { re: 0.0, im: 1.0 }))
+            }
+            Token::Identifier(name) => {
+                let value = session
+                    .variables
+                    .get(name)
+                    .ok_or_else(|| format!("Unknown variable '{}'", name))?;
+                stack.push(value.clone());
+            }
+            Token::Operator(op) => {
+                if stack.len() < 2 {
+                    return Err("Invalid expression format".to_string());
+                }
+                let mut operand2 = stack.pop().unwrap();
+                let operand1 = stack.pop().unwrap();
+                // `b%` is always b / 100: standalone `10%` is 0.1 and
+                // `200 * 10%` is 20. Under `mode finance`, a percentage that
+                // is the whole right operand of `+` or `-` is taken of the
+                // left operand instead, so `200 + 10%` is 220 and `200 - 10%`
+                // is 180; `200 + 10% * 2` is still 200.2. In RPN the token
+                // just before an operator ends its right operand, so that's
+                // the one to check.
+                let percent = index > 0 && tokens[index - 1] == Token::Postfix('%');
+                if session.finance_mode && percent && (op == "+" || op == "-") {
+                    operand2 = apply_operator("*", &operand1, &operand2, session)?;
+                }
+                stack.push(apply_operator(op, &operand1, &operand2, session)?);
+            }
+            Token::UnaryMinus => {
+                let operand = stack
+                    .pop()
+                    .ok_or_else(|| "Invalid expression format".to_string())?;
+                if let Value::List(_) = operand {
+                    return Err("Cannot negate a list".to_string());
+                }
+                let negated = match (&operand, operand.as_int().and_then(i64::checked_neg)) {
+                    (_, Some(n)) => Value::Int(n),
+                    (Value::Ratio(n, d), _) if *n != i64::MIN => Value::Ratio(-n, *d),
+                    (Value::Complex(z), _) => Value::Complex(Complex {
+                        re: -z.re,
+                        im: -z.im,
+                    }),
+                    // Adding 0.0 turns -0.0 into 0.0, so `-0.0` prints as 0
+                    _ => Value::Float(-operand.as_f64() + 0.0),
+                };
+                stack.push(negated);
+            }
+            Token::Postfix(op) => {
+                let operand = stack
+                    .pop()
+                    .ok_or_else(|| "Invalid expression format".to_string())?;
+                if let Value::List(_) = operand {
+                    return Err(format!("Operator '{}' is not supported for lists", op));
+                }
+                // Percentages stay exact where the division allows, so
+                // `50%` is 1/2 in `mode frac`
+                let value = match op {
+                    '%' => apply_operator("/", &operand, &Value::Int(100), session)?,
+                    _ => Value::Float(apply_postfix(*op, operand.as_f64(), session)?),
+                };
+                stack.push(value);
+            }
+            Token::Function(name, count) => {
+                if stack.len() < *count {
+                    return Err("Invalid expression format".to_string());
+                }
+                let args = stack.split_off(stack.len() - count);
+                if let Some(function) = session.functions.get(name) {
+                    stack.push(call_user_function(name, function, args, session)?);
+                    continue;
+                }
+                check_call(name, args.len())?;
+                if let Some(op) = operator_function(name) {
+                    let [operand1, operand2] = <[Value; 2]>::try_from(args)
+                        .map_err(|_| format!("Function '{}' expects 2 arguments", name))?;
+                    stack.push(apply_operator(op, &operand1, &operand2, session)?);
+                    continue;
+                }
+                // Aggregates take the elements of a list as their arguments
+                let args = if args.iter().any(|arg| matches!(arg, Value::List(_))) {
+                    if !is_aggregate(name) {
+                        return Err(format!("Function '{}' does not accept lists", name));
+                    }
+                    flatten(args)
+                } else {
+                    args
+                };
+                if let Some(result) = call_aggregate(name, &args, session) {
+                    stack.push(result?);
+                    continue;
+                }
+                let has_complex = args.iter().any(|arg| matches!(arg, Value::Complex(_)));
+                if session.complex_mode || has_complex {
+                    if let Some(result) = complex::call(name, &args) {
+                        stack.push(result?);
+                        continue;
+                    }
+                }
+                if has_complex {
+                    return Err(format!(
+                        "Function '{}' does not accept complex numbers",
+                        name
+                    ));
+                }
+                if let Some(result) = call_bitwise(name, &args) {
+                    stack.push(result?);
+                    continue;
+                }
+                if let Some(result) = call_integer_root(name, &args) {
+                    stack.push(result?);
+                    continue;
+                }
+                let args: Vec<f64> = args.iter().map(Value::as_f64).collect();
+                stack.push(call_function(name, &args, session)?);
+            }
+            _ => return Err("Invalid token in expression".to_string()),
+        }
+    }
+
+    if stack.len() != 1 {
+        return Err("Invalid expression format".to_string());
+    }
+
+    Ok(stack.pop().unwrap())
+}
+
+// Rough cost of evaluating a tree, for `max_cost`: one per token, plus
+// extra for each function call
+pub(crate) fn expression_cost(tree: &[Token]) -> usize {
+    tree.iter()
+        .map(|token| match token {
+            Token::Function(..) => 1 + FUNCTION_CALL_COST,
+            _ => 1,
+        })
+        .sum()
+}
+
+// Whether a tree's outermost operation is an inverse trig function, whose
+// result is an angle
+pub(crate) fn returns_angle(tree: &[Token]) -> bool {
+    matches!(
+        tree.last(),
+        Some(Token::Function(name, _)) if matches!(name.as_str(), "asin" | "acos" | "atan" | "atan2")
+    )
+}
+
+// Equal within `tolerance`, taken as absolute for small values and relative
+// to the larger magnitude otherwise
+pub(crate) fn approx_equal(a: f64, b: f64, tolerance: f64) -> bool {
+    a == b || (a - b).abs() <= tolerance * a.abs().max(b.abs()).max(1.0)
+}
+
+// Functions that treat a list argument as its elements
+fn is_aggregate(name: &str) -> bool {
+    matches!(
+        name,
+        "sum" | "max" | "min" | "mean" | "median" | "stddev" | "stddevs" | "stddevp"
+    )
+}
+
+// Splice the elements of lists into the argument list, nested lists included
+fn flatten(args: Vec<Value>) -> Vec<Value> {
+    let mut values = Vec::new();
+    for arg in args {
+        match arg {
+            Value::List(elements) => values.extend(flatten(elements)),
+            _ => values.push(arg),
+        }
+    }
+    values
+}
+
+// `sum`, `max` and `min`, which keep their arguments' type: the sum of
+// integers is exact and the maximum is one of the values as given
+fn call_aggregate(name: &str, args: &[Value], session: &Session) -> Option<Result<Value, String>> {
+    let pick = |keep_first: fn(f64, f64) -> bool| {
+        if args.iter().any(|arg| matches!(arg, Value::Complex(_))) {
+            return Err(format!(
+                "Function '{}' does not accept complex numbers",
+                name
+            ));
+        }
+        let (first, rest) = args
+            .split_first()
+            .ok_or_else(|| format!("Function '{}' expects at least 1 argument", name))?;
+        Ok(rest.iter().fold(first.clone(), |best, value| {
+            if keep_first(best.as_f64(), value.as_f64()) {
+                best
+            } else {
+                value.clone()
+            }
+        }))
+    };
+    Some(match name {
+        "sum" => args.iter().try_fold(Value::Int(0), |total, value| {
+            apply_operator("+", &total, value, session)
+        }),
+        "max" => pick(|best, value| best >= value),
+        "min" => pick(|best, value| best <= value),
+        _ => return None,
+    })
+}
+
+// Function forms of the arithmetic operators, e.g. `add(2, 3)` for `2 + 3`
+fn operator_function(name: &str) -> Option<&'static str> {
+    match name {
+        "add" => Some("+"),
+        "sub" => Some("-"),
+        "mul" => Some("*"),
+        "div" => Some("/"),
+        _ => None,
+    }
+}
+
+// Apply a binary operator; comparisons and logic give booleans, everything
+// else is arithmetic on the numeric values
+pub(crate) fn apply_operator(
+    op: &str,
+    operand1: &Value,
+    operand2: &Value,
+    session: &Session,
+) -> Result<Value, String> {
+    if let (Value::List(_), _) | (_, Value::List(_)) = (operand1, operand2) {
+        return Err(format!("Operator '{}' is not supported for lists", op));
+    }
+
+    if let (Value::Complex(_), _) | (_, Value::Complex(_)) = (operand1, operand2) {
+        return complex::apply(
+            op,
+            complex::from_value(operand1),
+            complex::from_value(operand2),
+        );
+    }
+
+    if let Some(custom) = session.custom_operator(op) {
+        return (custom.function)(operand1.as_f64(), operand2.as_f64()).map(Value::Float);
+    }
+
+    // Two integers compare exactly, even beyond the range f64 holds precisely
+    if let (Some(a), Some(b)) = (operand1.as_int(), operand2.as_int()) {
+        if let Some(result) = compare(op, &a, &b) {
+            return Ok(Value::Bool(result));
+        }
+    }
+
+    if op == "<<" || op == ">>" {
+        return shift(op, operand1, operand2).map(Value::Int);
+    }
+
+    let (a, b) = (operand1.as_f64(), operand2.as_f64());
+    if op == "~=" {
+        return Ok(Value::Bool(approx_equal(a, b, session.tolerance)));
+    }
+    if let Some(result) = compare(op, &a, &b) {
+        return Ok(Value::Bool(result));
+    }
+    let result = match op {
+        "and" => operand1.is_truthy() && operand2.is_truthy(),
+        "or" => operand1.is_truthy() || operand2.is_truthy(),
+        _ => {
+            if let (Some(a), Some(b)) = (operand1.as_int(), operand2.as_int()) {
+                if let Some(result) = apply_integer(op, a, b) {
+                    return Ok(Value::Int(result));
+                }
+            }
+            if session.frac_mode {
+                if let (Some(a), Some(b)) = (operand1.as_ratio(), operand2.as_ratio()) {
+                    if let Some(result) = ratio::apply(op, a, b) {
+                        return Ok(result);
+                    }
+                }
+            }
+            return apply_arithmetic(op, a, b, session).map(Value::Float);
+        }
+    };
+
+    Ok(Value::Bool(result))
+}
+
+// Infinities order below and above every finite number; NaN compares false
+// with everything, itself included
+fn compare<T: PartialOrd>(op: &str, a: &T, b: &T) -> Option<bool> {
+    match op {
+        "==" => Some(a == b),
+        "!=" => Some(a != b),
+        "<" => Some(a < b),
+        "<=" => Some(a <= b),
+        ">" => Some(a > b),
+        ">=" => Some(a >= b),
+        _ => None,
+    }
+}
+
+// `a << b` and `a >> b` on integers, with `>>` keeping the sign. Bits
+// shifted past the top are an error rather than lost.
+fn shift(op: &str, a: &Value, b: &Value) -> Result<i64, String> {
+    let (Some(a), Some(b)) = (whole_value(a), whole_value(b)) else {
+        return Err(format!("Operator '{}' expects integers", op));
+    };
+    if b < 0 {
+        return Err("Shift amount must not be negative".to_string());
+    }
+    if op == ">>" {
+        return Ok(a >> b.min(63));
+    }
+    let shifted = u32::try_from(b)
+        .ok()
+        .and_then(|b| a.checked_shl(b))
+        .filter(|shifted| shifted >> b == a);
+    match shifted {
+        Some(shifted) => Ok(shifted),
+        None if a == 0 => Ok(0),
+        None => Err("Integer overflow".to_string()),
+    }
+}
+
+// An integer, or a float with no fractional part such as `8.0`, as an i64
+fn whole_value(value: &Value) -> Option<i64> {
+    match value {
+        Value::Float(x) if x.fract() == 0.0 && x.abs() < i64::MAX as f64 => Some(*x as i64),
+        _ => value.as_int(),
+    }
+}
+
+// Exact integer arithmetic. None means the result would overflow or isn't
+// a whole number (`7 / 2`, `2 ^ -1`), so the caller promotes to a float.
+fn apply_integer(op: &str, a: i64, b: i64) -> Option<i64> {
+    match op {
+        "+" => a.checked_add(b),
+        "-" => a.checked_sub(b),
+        "*" => a.checked_mul(b),
+        "/" if a.checked_rem(b)? == 0 => a.checked_div(b),
+        "//" => {
+            let quotient = a.checked_div(b)?;
+            // Round toward negative infinity like the float path
+            if a % b != 0 && (a < 0) != (b < 0) {
+                Some(quotient - 1)
+            } else {
+                Some(quotient)
+            }
+        }
+        "mod" => {
+            let remainder = a.checked_rem(b)?;
+            if remainder != 0 && (remainder < 0) != (b < 0) {
+                Some(remainder + b)
+            } else {
+                Some(remainder)
+            }
+        }
+        "^" => a.checked_pow(u32::try_from(b).ok()?),
+        _ => None,
+    }
+}
+
+fn apply_arithmetic(
+    op: &str,
+    operand1: f64,
+    operand2: f64,
+    session: &Session,
+) -> Result<f64, String> {
+    if matches!(op, "/" | "//" | "mod") && operand2 == 0.0 {
+        return Err("Division by zero".to_string());
+    }
+
+    let decimal_result = session
+        .decimal_scale
+        .and_then(|scale| decimal::apply(op, operand1, operand2, scale));
+    let result = match (op, decimal_result) {
+        (_, Some(result)) => result,
+        ("+", _) => operand1 + operand2,
+        ("-", _) => operand1 - operand2,
+        ("*", _) => operand1 * operand2,
+        ("/", _) => operand1 / operand2,
+        // Floor division, as in Python: 7 // 2 is 3 and -7 // 2 is -4
+        ("//", _) => (operand1 / operand2).floor(),
+        // The remainder to match, taking the divisor's sign: -7 mod 3 is 2
+        ("mod", _) => {
+            let remainder = operand1 % operand2;
+            if remainder != 0.0 && (remainder < 0.0) != (operand2 < 0.0) {
+                remainder + operand2
+            } else {
+                remainder
+            }
+        }
+        ("^", _) => operand1.powf(operand2),
+        _ => return Err("Invalid operator".to_string()),
+    };
+
+    // Infinities follow IEEE 754: 1 / inf is 0 and inf - inf is NaN, which
+    // strict mode turns into an error
+    if session.strict_mode && !result.is_finite() {
+        return Err("Result is not a finite number".to_string());
+    }
+
+    if session.warn_mode
+        && op == "/"
+        && operand1.fract() == 0.0
+        && operand2.fract() == 0.0
+        && result.fract() != 0.0
+    {
+        session.add_note(format!(
+            "note: {} / {} = {} (use // for integer division)",
+            operand1, operand2, result
+        ));
+    }
+
+    Ok(result)
+}
+
+// Apply a postfix operator to its operand
+fn apply_postfix(op: char, operand: f64, session: &Session) -> Result<f64, String> {
+    match op {
+        // The operand is in degrees whatever the angle mode; convert it to the
+        // unit trig functions expect, so `sin(90°)` is 1 in both modes
+        '°' => match session.angle_mode {
+            AngleMode::Radians => Ok(operand.to_radians()),
+            AngleMode::Degrees => Ok(operand),
+        },
+        _ => Err("Invalid operator".to_string()),
+    }
+}
+
+// Built-in named constants
+pub(crate) fn constant(name: &str) -> Option<Value> {
+    match name {
+        "pi" => Some(Value::Float(std::f64::consts::PI)),
+        "e" => Some(Value::Float(std::f64::consts::E)),
+        "inf" => Some(Value::Float(f64::INFINITY)),
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        _ => None,
+    }
+}
+
+// Call a built-in function
+fn call_function(name: &str, args: &[f64], session: &Session) -> Result<Value, String> {
+    // Trig inputs and inverse-trig outputs follow the session's angle mode
+    let to_radians = |x: f64| match session.angle_mode {
+        AngleMode::Radians => x,
+        AngleMode::Degrees => x.to_radians(),
+    };
+    let from_radians = |x: f64| match session.angle_mode {
+        AngleMode::Radians => x,
+        AngleMode::Degrees => x.to_degrees(),
+    };
+
+    let result = match name {
+        "sin" => to_radians(single(name, args)?).sin(),
+        "cos" => to_radians(single(name, args)?).cos(),
+        "tan" => to_radians(single(name, args)?).tan(),
+        "asin" => from_radians(single(name, args)?.asin()),
+        "acos" => from_radians(single(name, args)?.acos()),
+        "atan" => from_radians(single(name, args)?.atan()),
+        // Quadrant-aware; atan2(0, 0) is defined as 0
+        "atan2" => {
+            let [y, x] = pair(name, args)?;
+            from_radians(y.atan2(x))
+        }
+        "not" => return Ok(Value::Bool(single(name, args)? == 0.0)),
+        "sqrt" => {
+            let x = single(name, args)?;
+            if x < 0.0 {
+                return Err("Square root of a negative number".to_string());
+            }
+            x.sqrt()
+        }
+        "abs" => single(name, args)?.abs(),
+        "exp" => single(name, args)?.exp(),
+        // Natural and base-10 logarithms
+        "ln" | "log" => {
+            let x = single(name, args)?;
+            if x <= 0.0 {
+                return Err("Logarithm of a non-positive number".to_string());
+            }
+            if name == "ln" {
+                x.ln()
+            } else {
+                x.log10()
+            }
+        }
+        // Nearest multiple of an increment: round_to(7.3, 0.25) is 7.25
+        "round_to" => {
+            let [value, nearest] = pair(name, args)?;
+            if nearest == 0.0 {
+                return Err("Rounding increment cannot be zero".to_string());
+            }
+            (value / nearest).round() * nearest
+        }
+        // Percentage change from the first value to the second
+        "pctchange" => {
+            let [old, new] = pair(name, args)?;
+            if old == 0.0 {
+                return Err("Percentage change from zero is undefined".to_string());
+            }
+            (new - old) / old * 100.0
+        }
+        // Whole and fractional parts, both taking the sign of x:
+        // intpart(-1.25) is -1 and fracpart(-1.25) is -0.25
+        "intpart" => single(name, args)?.trunc(),
+        "fracpart" => single(name, args)?.fract(),
+        // Clamped to [0, 1]; NaN stays NaN
+        "saturate" => single(name, args)?.clamp(0.0, 1.0),
+        // Linear interpolation, t = 0 giving a and t = 1 giving b
+        "lerp" => {
+            let [a, b, t] = triple(name, args)?;
+            a + (b - a) * t
+        }
+        "mean" => mean(at_least(name, args, 1)?),
+        // Middle value, or the average of the two middle values for an even count
+        "median" => {
+            let mut sorted = at_least(name, args, 1)?.to_vec();
+            sorted.sort_by(f64::total_cmp);
+            let middle = sorted.len() / 2;
+            if sorted.len() % 2 == 0 {
+                (sorted[middle - 1] + sorted[middle]) / 2.0
+            } else {
+                sorted[middle]
+            }
+        }
+        // Population standard deviation, dividing by n
+        "stddevp" => variance(at_least(name, args, 1)?, 0).sqrt(),
+        // Sample standard deviation, dividing by n - 1; `stddev` is the same
+        "stddev" | "stddevs" => variance(at_least(name, args, 2)?, 1).sqrt(),
+        // Uniform in [0, 1)
+        "random" => {
+            none(name, args)?;
+            session.next_random()
+        }
+        // `pi()` and friends mean the same as the bare constant
+        _ if constant(name).is_some() => {
+            none(name, args)?;
+            return Ok(constant(name).unwrap());
+        }
+        _ => return Err(format!("Unknown function '{}'", name)),
+    };
+
+    Ok(Value::Float(result))
+}
+
+// Bitwise functions on the i64 value of whole-number arguments; None if
+// `name` isn't one of them
+fn call_bitwise(name: &str, args: &[Value]) -> Option<Result<Value, String>> {
+    let integers = || {
+        args.iter()
+            .map(whole_value)
+            .collect::<Option<Vec<i64>>>()
+            .ok_or_else(|| format!("Function '{}' expects integers", name))
+    };
+    let pair = || match integers()?.as_slice() {
+        [a, b] => Ok((*a, *b)),
+        _ => Err(format!("Function '{}' expects 2 arguments", name)),
+    };
+
+    let result = match name {
+        "bitand" => pair().map(|(a, b)| a & b),
+        "bitor" => pair().map(|(a, b)| a | b),
+        "bitxor" => pair().map(|(a, b)| a ^ b),
+        "bitnot" => integers().and_then(|values| match values.as_slice() {
+            [a] => Ok(!a),
+            _ => Err(format!("Function '{}' expects 1 argument", name)),
+        }),
+        _ => return None,
+    };
+    Some(result.map(Value::Int))
+}
+
+// `isqrt` and `isperfectsquare` on the exact integer, before any rounding
+// to f64 loses the low digits; None if `name` isn't one of them
+fn call_integer_root(name: &str, args: &[Value]) -> Option<Result<Value, String>> {
+    if !matches!(name, "isqrt" | "isperfectsquare") {
+        return None;
+    }
+    let n = match args {
+        [arg] => whole_number(name, arg),
+        _ => Err(format!("Function '{}' expects 1 argument", name)),
+    };
+
+    Some(n.map(|n| {
+        let root = integer_sqrt(n);
+        match name {
+            "isqrt" => Value::Int(root as i64),
+            _ => Value::Int((root * root == n) as i64),
+        }
+    }))
+}
+
+// Floor of the square root by Newton's method on integers, exact where
+// `sqrt(n).floor()` can be off by one for large n
+fn integer_sqrt(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+
+    let n = n as u128;
+    let mut x = n;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x as u64
+}
+
+// The argument as a non-negative integer, for number-theory functions
+fn whole_number(name: &str, arg: &Value) -> Result<u64, String> {
+    let n = match arg {
+        Value::Float(x) if x.fract() == 0.0 && *x >= 0.0 && *x < u64::MAX as f64 => Some(*x as u64),
+        _ => arg.as_int().and_then(|n| u64::try_from(n).ok()),
+    };
+    n.ok_or_else(|| format!("Function '{}' expects a non-negative integer", name))
+}
+
+pub(crate) fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+// Sum of squared deviations from the mean over `n - correction`
+pub(crate) fn variance(values: &[f64], correction: usize) -> f64 {
+    let mean = mean(values);
+    let squares: f64 = values.iter().map(|x| (x - mean).powi(2)).sum();
+    squares / (values.len() - correction) as f64
+}
+
+fn at_least<'a>(name: &str, args: &'a [f64], count: usize) -> Result<&'a [f64], String> {
+    if args.len() < count {
+        let plural = if count == 1 { "" } else { "s" };
+        return Err(format!(
+            "Function '{}' expects at least {} argument{}",
+            name, count, plural
+        ));
+    }
+    Ok(args)
+}
+
+fn none(name: &str, args: &[f64]) -> Result<(), String> {
+    match args {
+        [] => Ok(()),
+        _ => Err(format!("Function '{}' expects no arguments", name)),
+    }
+}
+
+fn single(name: &str, args: &[f64]) -> Result<f64, String> {
+    match args {
+        [x] => Ok(*x),
+        _ => Err(format!("Function '{}' expects 1 argument", name)),
+    }
+}
+
+fn pair(name: &str, args: &[f64]) -> Result<[f64; 2], String> {
+    match args {
+        [x, y] => Ok([*x, *y]),
+        _ => Err(format!("Function '{}' expects 2 arguments", name)),
+    }
+}
+
+fn triple(name: &str, args: &[f64]) -> Result<[f64; 3], String> {
+    match args {
+        [x, y, z] => Ok([*x, *y, *z]),
+        _ => Err(format!("Function '{}' expects 3 arguments", name)),
+    }
+}
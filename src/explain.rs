@@ -0,0 +1,143 @@
+use crate::eval::{apply_operator, evaluate_tree};
+use crate::format::format_result;
+use crate::lexer::Token;
+use crate::parse_within_limits;
+use crate::parser::precedence;
+use crate::session::Session;
+use crate::value::Value;
+
+// One operation in evaluation order
+struct Step {
+    op: String,
+    left: Operand,
+    right: Operand,
+    result: String,
+}
+
+// A value on the stack, remembering which step produced it, if any
+struct Operand {
+    value: Value,
+    text: String,
+    step: Option<usize>,
+}
+
+// `explain <expr>`: describe in words the order the operations are carried
+// out in, e.g. "First, because of precedence, we multiply 3 by 4 to get 12."
+pub(crate) fn explain(input: &str, session: &Session) -> Result<String, String> {
+    let expression = input.trim_start().strip_prefix("explain").unwrap_or("");
+    if expression.trim().is_empty() {
+        return Err("Usage: explain <expression>".to_string());
+    }
+    let tree = parse_within_limits(expression, session)?;
+
+    let mut stack: Vec<Operand> = Vec::new();
+    let mut steps: Vec<Step> = Vec::new();
+    for token in &tree {
+        match token {
+            Token::Integer(_) | Token::Number(_) | Token::Identifier(_) => {
+                let value = evaluate_tree(std::slice::from_ref(token), session)?;
+                let text = format_result(&value, session)?;
+                stack.push(Operand {
+                    value,
+                    text,
+                    step: None,
+                });
+            }
+            Token::UnaryMinus => {
+                let operand = stack
+                    .pop()
+                    .ok_or_else(|| "Invalid expression format".to_string())?;
+                let value = apply_operator("-", &Value::Int(0), &operand.value, session)?;
+                let text = format_result(&value, session)?;
+                stack.push(Operand {
+                    value,
+                    text,
+                    ..operand
+                });
+            }
+            Token::Operator(op) if matches!(op.as_str(), "+" | "-" | "*" | "/") => {
+                let (Some(right), Some(left)) = (stack.pop(), stack.pop()) else {
+                    return Err("Invalid expression format".to_string());
+                };
+                let value = apply_operator(op, &left.value, &right.value, session)?;
+                let result = format_result(&value, session)?;
+                stack.push(Operand {
+                    value,
+                    text: result.clone(),
+                    step: Some(steps.len()),
+                });
+                steps.push(Step {
+                    op: op.clone(),
+                    left,
+                    right,
+                    result,
+                });
+            }
+            _ => return Err("explain only covers + - * / and parentheses".to_string()),
+        }
+    }
+
+    let [answer] = stack.as_slice() else {
+        return Err("Invalid expression format".to_string());
+    };
+    if steps.is_empty() {
+        return Ok(format!(
+            "There is nothing to work out: the result is {}.",
+            answer.text
+        ));
+    }
+
+    let sentences: Vec<String> = steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| describe(index, step, &steps, session))
+        .collect();
+    Ok(sentences.join(" "))
+}
+
+fn describe(index: usize, step: &Step, steps: &[Step], session: &Session) -> String {
+    let opening = match index {
+        _ if steps.len() == 1 => "",
+        0 => "First, ",
+        _ if index == steps.len() - 1 => "Finally, ",
+        _ => "Then ",
+    };
+
+    // Done before a looser operator means precedence decided the order;
+    // done before a tighter one means parentheses did
+    let own = precedence(&step.op, session);
+    let later = &steps[index + 1..];
+    let reason = if later.iter().any(|next| precedence(&next.op, session) < own) {
+        "because of precedence, "
+    } else if later.iter().any(|next| precedence(&next.op, session) > own) {
+        "because of the parentheses, "
+    } else {
+        ""
+    };
+
+    // The result of the step just before reads as carrying on from it
+    let previous = index.checked_sub(1);
+    let left_is_previous = step.left.step.is_some() && step.left.step == previous;
+    let right_is_previous = step.right.step.is_some() && step.right.step == previous;
+    let (left, right) = (&step.left.text, &step.right.text);
+    let action = match (step.op.as_str(), left_is_previous, right_is_previous) {
+        ("+", true, _) => format!("add {}", right),
+        ("+", _, true) => format!("add {}", left),
+        ("+", _, _) => format!("add {} and {}", left, right),
+        ("-", true, _) => format!("subtract {}", right),
+        ("-", _, true) => format!("subtract that from {}", left),
+        ("-", _, _) => format!("subtract {} from {}", right, left),
+        ("*", true, _) => format!("multiply by {}", right),
+        ("*", _, true) => format!("multiply by {}", left),
+        ("*", _, _) => format!("multiply {} by {}", left, right),
+        ("/", true, _) => format!("divide by {}", right),
+        ("/", _, true) => format!("divide {} by that", left),
+        _ => format!("divide {} by {}", left, right),
+    };
+
+    let subject = if opening.is_empty() { "We" } else { "we" };
+    format!(
+        "{}{}{} {} to get {}.",
+        opening, reason, subject, action, step.result
+    )
+}
@@ -0,0 +1,393 @@
+use crate::decimal::Decimal;
+use crate::session::{DisplayMode, Session};
+use crate::value::Value;
+
+// Decimal places kept in a scientific or engineering-notation mantissa
+const DEFAULT_ENG_PRECISION: usize = 6;
+
+// Decimal places in fixed notation when no precision is set
+const DEFAULT_FIXED_PRECISION: usize = 2;
+
+/// Format a result according to the session's display settings.
+///
+/// Only whole numbers can be shown in base 2, 8 or 16. Anything else falls
+/// back to decimal, or is an error under `mode strictbase`.
+///
+/// ```
+/// use calc::{format_result, DisplayMode, Session, Value};
+///
+/// let mut session = Session::new();
+/// session.display_mode = DisplayMode::Currency;
+/// session.currency_symbol = "€".to_string();
+/// session.group_separator = '.';
+/// session.decimal_separator = ',';
+/// let amount = Value::Float(1234.56);
+/// assert_eq!(format_result(&amount, &session), Ok("€1.234,56".to_string()));
+/// // Cents round half up in decimal, and amounts too large for cents keep
+/// // their symbol
+/// assert_eq!(format_result(&Value::Float(1.005), &session), Ok("€1,01".to_string()));
+/// assert_eq!(format_result(&Value::Float(-1e300), &session), Ok("-€1e300".to_string()));
+///
+/// session.display_mode = DisplayMode::Auto;
+/// assert_eq!(format_result(&Value::Float(2.5), &session), Ok("2,5".to_string()));
+/// // List items are still separated by ", "
+/// let list = Value::List(vec![Value::Float(1.5), Value::Float(2.25)]);
+/// assert_eq!(format_result(&list, &session), Ok("1,5, 2,25".to_string()));
+/// ```
+///
+/// In another base, `mode strictbase` refuses a fraction rather than showing
+/// it in decimal:
+///
+/// ```
+/// use calc::{format_result, Session, Value};
+///
+/// let mut session = Session::new();
+/// session.output_base = 16;
+/// assert_eq!(format_result(&Value::Int(255), &session), Ok("0xFF".to_string()));
+/// assert_eq!(format_result(&Value::Float(2.5), &session), Ok("2.5".to_string()));
+/// session.strict_base = true;
+/// assert_eq!(format_result(&Value::Float(16.0), &session), Ok("0x10".to_string()));
+/// assert_eq!(
+///     format_result(&Value::Float(2.5), &session),
+///     Err("Cannot display non-integer in base 16".to_string())
+/// );
+/// ```
+///
+/// Negative numbers in another base have a sign, or under `mode
+/// twoscomplement` are shown as their 64-bit two's complement:
+///
+/// ```
+/// use calc::{evaluate_expression, format_result, Session};
+///
+/// let mut session = Session::new();
+/// session.output_base = 16;
+/// let value = evaluate_expression("-255", &session).unwrap();
+/// assert_eq!(format_result(&value, &session), Ok("-0xFF".to_string()));
+/// session.twos_complement = true;
+/// assert_eq!(format_result(&value, &session), Ok("0xFFFFFFFFFFFFFF01".to_string()));
+/// session.output_base = 2;
+/// let value = evaluate_expression("-1", &session).unwrap();
+/// assert_eq!(format_result(&value, &session), Ok(format!("0b{}", "1".repeat(64))));
+/// ```
+///
+/// Comparisons print as 1 or 0, or as true or false in `mode bool`:
+///
+/// ```
+/// use calc::{evaluate_expression, format_result, Session};
+///
+/// let mut session = Session::new();
+/// let compare = |input, session: &Session| {
+///     format_result(&evaluate_expression(input, session).unwrap(), session).unwrap()
+/// };
+/// assert_eq!(compare("3 > 2", &session), "1");
+/// assert_eq!(compare("1 + 1 != 2", &session), "0");
+/// session.bool_mode = true;
+/// assert_eq!(compare("3 > 2", &session), "true");
+/// assert_eq!(compare("1 + 1 != 2", &session), "false");
+/// assert_eq!(compare("(3 > 2) + 1", &session), "2");
+/// ```
+///
+/// Engineering notation keeps the exponent a multiple of 3:
+///
+/// ```
+/// use calc::{format_result, DisplayMode, Session, Value};
+///
+/// let mut session = Session::new();
+/// session.display_mode = DisplayMode::Engineering;
+/// let show = |x: f64, session: &Session| format_result(&Value::Float(x), session).unwrap();
+/// assert_eq!(show(1500.0, &session), "1.5e3");
+/// assert_eq!(show(0.00012, &session), "120e-6");
+/// assert_eq!(show(-47000.0, &session), "-47e3");
+/// // Rounding that carries into the next power moves the exponent up
+/// assert_eq!(show(999.9999999, &session), "1e3");
+/// ```
+///
+/// With no precision set, a float is written in full with Rust's shortest
+/// exact digits; `precision auto` writes very large and very small ones with
+/// an exponent instead:
+///
+/// ```
+/// use calc::{format_result, run_command, Session, Value};
+///
+/// let mut session = Session::new();
+/// let show = |x: f64, session: &Session| format_result(&Value::Float(x), session).unwrap();
+/// assert_eq!(show(0.000001, &session), "0.000001");
+/// assert_eq!(show(1e20, &session), "100000000000000000000");
+/// assert_eq!(show(123456789012345678.0, &session), "123456789012345680");
+///
+/// run_command("precision auto", &mut session);
+/// assert_eq!(show(0.000001, &session), "1e-6");
+/// assert_eq!(show(1e20, &session), "1e20");
+/// assert_eq!(show(0.1 + 0.2, &session), "0.30000000000000004");
+/// run_command("precision 2", &mut session);
+/// assert_eq!(show(1e20, &session), "100000000000000000000");
+/// ```
+pub fn format_result(value: &Value, session: &Session) -> Result<String, String> {
+    // Localise each item, so the ", " between them is left alone
+    if let Value::List(values) = value {
+        let parts = values
+            .iter()
+            .map(|value| format_result(value, session))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(parts.join(", "));
+    }
+
+    let text = format_unlocalized(value, session)?;
+    if session.group_separator == ',' && session.decimal_separator == '.' {
+        return Ok(text);
+    }
+    // Swap both at once, so `,` -> `.` and `.` -> `,` don't undo each other
+    Ok(text
+        .chars()
+        .map(|c| match c {
+            '.' => session.decimal_separator,
+            ',' => session.group_separator,
+            _ => c,
+        })
+        .collect())
+}
+
+// The text with `,` grouping and `.` before the fraction
+fn format_unlocalized(value: &Value, session: &Session) -> Result<String, String> {
+    // Each part is formatted like a real number: 3+4i, 0.5-2i, 4i
+    if let Value::Complex(z) = value {
+        let im = format_unlocalized(&Value::Float(z.im.abs()), session)?;
+        if z.re == 0.0 {
+            let sign = if z.im < 0.0 { "-" } else { "" };
+            return Ok(format!("{}{}i", sign, im));
+        }
+        let re = format_unlocalized(&Value::Float(z.re), session)?;
+        let sign = if z.im < 0.0 { '-' } else { '+' };
+        return Ok(format!("{}{}{}i", re, sign, im));
+    }
+
+    if let Value::Bool(b) = value {
+        if session.bool_mode {
+            return Ok(b.to_string());
+        }
+    }
+
+    if let (Some(digits), DisplayMode::Auto, 10) =
+        (session.sigfigs, session.display_mode, session.output_base)
+    {
+        if !matches!(value, Value::Bool(_)) {
+            return Ok(format_sigfigs(value.as_f64(), digits));
+        }
+    }
+
+    if let (Value::Ratio(n, d), DisplayMode::Auto) = (value, session.display_mode) {
+        return Ok(format!("{}/{}", n, d));
+    }
+
+    if let (Some(n), DisplayMode::Auto) = (value.as_int(), session.display_mode) {
+        return Ok(match session.output_base {
+            10 => n.to_string(),
+            base => format_int_in_base(n, base, session.twos_complement)
+                .unwrap_or_else(|| n.to_string()),
+        });
+    }
+
+    let value = value.as_f64();
+
+    if session.output_base != 10 {
+        if let Some(text) = format_in_base(value, session.output_base, session.twos_complement) {
+            return Ok(text);
+        }
+        if session.strict_base {
+            return Err(format!(
+                "Cannot display non-integer in base {}",
+                session.output_base
+            ));
+        }
+    }
+
+    Ok(match session.display_mode {
+        DisplayMode::Auto => match session.precision {
+            Some(places) => trim_zeros(format!("{:.*}", places, value)),
+            None if session.shortest => format_shortest(value),
+            None => value.to_string(),
+        },
+        DisplayMode::Fixed => format!(
+            "{:.*}",
+            session.precision.unwrap_or(DEFAULT_FIXED_PRECISION),
+            value
+        ),
+        DisplayMode::Scientific => {
+            format_scientific(value, session.precision.unwrap_or(DEFAULT_ENG_PRECISION))
+        }
+        DisplayMode::Engineering => {
+            format_engineering(value, session.precision.unwrap_or(DEFAULT_ENG_PRECISION))
+        }
+        DisplayMode::Currency => format_currency(value, &session.currency_symbol),
+    })
+}
+
+// Two decimal places, rounded half up in decimal rather than binary (so 1.005
+// becomes 1.01), with commas between thousands: 1234.5 -> $1,234.50
+fn format_currency(value: f64, symbol: &str) -> String {
+    let Some(cents) = Decimal::from_f64(value)
+        .and_then(|amount| amount.round_to(2))
+        .and_then(|amount| amount.rescale(2))
+    else {
+        // Too large to count in cents, but still an amount of money
+        let sign = if value < 0.0 { "-" } else { "" };
+        return format!(
+            "{}{}{}",
+            sign,
+            symbol,
+            format_scientific(value.abs(), DEFAULT_FIXED_PRECISION)
+        );
+    };
+
+    let text = cents.to_string();
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(magnitude) => ("-", magnitude),
+        None => ("", text.as_str()),
+    };
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, "00"));
+
+    let mut grouped = String::new();
+    for (index, digit) in whole.chars().enumerate() {
+        if index > 0 && (whole.len() - index) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+
+    format!("{}{}{}.{}", sign, symbol, grouped, fraction)
+}
+
+// Whole numbers in base 2, 8 or 16 with a `0b`/`0o`/`0x` prefix. Anything
+// else returns None and is shown in decimal.
+fn format_in_base(value: f64, base: u32, twos_complement: bool) -> Option<String> {
+    if value.fract() != 0.0 || value.abs() >= i64::MAX as f64 {
+        return None;
+    }
+
+    format_int_in_base(value as i64, base, twos_complement)
+}
+
+// Negative numbers are shown as a sign and magnitude (-255 is -0xFF), or as
+// their 64-bit two's complement (0xFFFFFFFFFFFFFF01)
+fn format_int_in_base(value: i64, base: u32, twos_complement: bool) -> Option<String> {
+    let (sign, magnitude) = match value {
+        _ if twos_complement => ("", value as u64),
+        _ if value < 0 => ("-", value.unsigned_abs()),
+        _ => ("", value as u64),
+    };
+    let digits = match base {
+        2 => format!("0b{:b}", magnitude),
+        8 => format!("0o{:o}", magnitude),
+        16 => format!("0x{:X}", magnitude),
+        _ => return None,
+    };
+    Some(format!("{}{}", sign, digits))
+}
+
+// 2.500 -> 2.5, 3.000 -> 3
+fn trim_zeros(text: String) -> String {
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
+
+// Format as `<mantissa>e<exponent>` with one digit before the point,
+// e.g. 1500 -> 1.5e3
+fn format_scientific(value: f64, precision: usize) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+
+    let text = format!("{:.*e}", precision, value);
+    match text.split_once('e') {
+        Some((mantissa, exponent)) => {
+            format!("{}e{}", trim_zeros(mantissa.to_string()), exponent)
+        }
+        None => text,
+    }
+}
+
+/// Format a number to `digits` significant figures, keeping trailing zeros
+/// that count. Very large and very small magnitudes are written as
+/// `<mantissa>e<exponent>`.
+///
+/// ```
+/// use calc::format_sigfigs;
+///
+/// assert_eq!(format_sigfigs(1234.0, 3), "1230");
+/// assert_eq!(format_sigfigs(0.0012345, 3), "0.00123");
+/// assert_eq!(format_sigfigs(2.0, 3), "2.00");
+/// assert_eq!(format_sigfigs(-98765.4, 2), "-99000");
+/// assert_eq!(format_sigfigs(0.0, 3), "0.00");
+/// assert_eq!(format_sigfigs(6.02214076e23, 4), "6.022e23");
+/// assert_eq!(format_sigfigs(1.602e-19, 2), "1.6e-19");
+/// ```
+pub fn format_sigfigs(value: f64, digits: usize) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+
+    // Rounding in scientific notation first settles the exponent, carries
+    // included (9.99 at 2 figures is 1.0e1)
+    let digits = digits.max(1);
+    let text = format!("{:.*e}", digits - 1, value);
+    let (mantissa, exponent) = text.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    if !(-5..15).contains(&exponent) {
+        return text;
+    }
+
+    let places = (digits as i32 - 1 - exponent).max(0) as usize;
+    let rounded: f64 = format!("{}e{}", mantissa, exponent).parse().unwrap();
+    format!("{:.*}", places, rounded)
+}
+
+/// The fewest significant figures that parse back to exactly the same
+/// number, as `precision auto` shows a result. These are the digits Rust's
+/// `{}` picks, but very large and very small magnitudes are written as
+/// `<mantissa>e<exponent>` rather than in full.
+///
+/// ```
+/// use calc::format_shortest;
+///
+/// for value in [0.1, 1.0 / 3.0, 0.1 + 0.2, 2.0f64.sqrt(), 1e-300, -5e20] {
+///     assert_eq!(format_shortest(value).parse::<f64>(), Ok(value));
+/// }
+/// assert_eq!(format_shortest(0.1), "0.1");
+/// assert_eq!(format_shortest(1.0 / 3.0), "0.3333333333333333");
+/// assert_eq!(format_shortest(0.1 + 0.2), "0.30000000000000004");
+/// assert_eq!(format_shortest(1500.0), "1500");
+/// assert_eq!(format_shortest(-5e20), "-5e20");
+/// assert_eq!(format_shortest(1.5e-7), "1.5e-7");
+/// ```
+pub fn format_shortest(value: f64) -> String {
+    // `{:e}` keeps the same shortest digits and says where the point goes
+    let text = format!("{:e}", value);
+    match text
+        .split_once('e')
+        .map(|(_, exponent)| exponent.parse::<i32>())
+    {
+        Some(Ok(exponent)) if !(-5..15).contains(&exponent) => text,
+        _ => value.to_string(),
+    }
+}
+
+// Format as `<mantissa>e<exponent>` with the exponent a multiple of 3,
+// e.g. 1500 -> 1.5e3 and 0.00012 -> 120e-6
+pub(crate) fn format_engineering(value: f64, precision: usize) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return value.to_string();
+    }
+
+    let mut exponent = (value.abs().log10().floor() as i32).div_euclid(3) * 3;
+    let mut mantissa = format!("{:.*}", precision, value / 10f64.powi(exponent));
+    // Rounding can carry the mantissa up to 1000, e.g. 999.9999999
+    if mantissa.parse::<f64>().unwrap().abs() >= 1000.0 {
+        exponent += 3;
+        mantissa = format!("{:.*}", precision, value / 10f64.powi(exponent));
+    }
+
+    format!("{}e{}", trim_zeros(mantissa), exponent)
+}
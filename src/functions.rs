@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use crate::eval::{constant, evaluate_scoped};
+use crate::lexer::{is_identifier, Token};
+use crate::parse_within_limits;
+use crate::session::{split_call, Session};
+use crate::value::Value;
+
+/// A function defined in the REPL with `name(params) = body`
+#[derive(Debug, Clone)]
+pub(crate) struct UserFunction {
+    params: Vec<String>,
+    // Parsed once at definition time
+    body: Vec<Token>,
+}
+
+// Handle `f(x, y) = body`; returns None when the input is not a definition
+pub(crate) fn define_function(
+    input: &str,
+    session: &mut Session,
+) -> Option<Result<String, String>> {
+    let index = input.find('=')?;
+    let (target, body) = (&input[..index], &input[index + 1..]);
+    if body.starts_with('=') {
+        return None;
+    }
+    let (name, params) = split_call(target)?;
+
+    Some(define(name, &params, body, session))
+}
+
+fn define(
+    name: &str,
+    params: &[&str],
+    body: &str,
+    session: &mut Session,
+) -> Result<String, String> {
+    if constant(name).is_some() {
+        return Err(format!("Cannot redefine constant '{}'", name));
+    }
+
+    // `f()` splits into one empty argument, but defines a function of none
+    let params = match params {
+        [only] if only.trim().is_empty() => &[],
+        _ => params,
+    };
+    let mut names: Vec<String> = Vec::new();
+    for param in params {
+        let param = param.trim();
+        if !is_identifier(param) || constant(param).is_some() {
+            return Err(format!("Invalid parameter '{}'", param));
+        }
+        if names.iter().any(|existing| existing == param) {
+            return Err(format!("Duplicate parameter '{}'", param));
+        }
+        names.push(param.to_string());
+    }
+
+    let body = parse_within_limits(body.trim(), session)?;
+    let signature = format!("{}({})", name, names.join(", "));
+    session.functions.insert(
+        name.to_string(),
+        UserFunction {
+            params: names,
+            body,
+        },
+    );
+    // Cached results may have used an earlier definition
+    if let Some(cache) = session.cache.as_mut() {
+        cache.clear();
+    }
+
+    Ok(format!("Defined {}", signature))
+}
+
+// Evaluate a user function's body with its parameters bound to the arguments.
+// Every call counts against the session's budget, so runaway recursion ends
+// in an error rather than a stack overflow.
+pub(crate) fn call_user_function(
+    name: &str,
+    function: &UserFunction,
+    args: Vec<Value>,
+    session: &Session,
+) -> Result<Value, String> {
+    if args.len() != function.params.len() {
+        let plural = if function.params.len() == 1 { "" } else { "s" };
+        return Err(format!(
+            "Function '{}' expects {} argument{}",
+            name,
+            function.params.len(),
+            plural
+        ));
+    }
+
+    let locals: HashMap<String, Value> = function.params.iter().cloned().zip(args).collect();
+    session.guard_call(|| evaluate_scoped(&function.body, session, &locals))
+}
@@ -0,0 +1,455 @@
+// What `help <name>` knows about each built-in function and operator, and
+// the argument counts the evaluator checks calls against: a function is
+// callable only if it has an entry here.
+struct Topic {
+    name: &'static str,
+    usage: &'static str,
+    arity: Arity,
+    description: &'static str,
+    example: &'static str,
+}
+
+#[derive(Clone, Copy)]
+enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    Operands(usize),
+}
+
+impl std::fmt::Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        match *self {
+            Arity::Exactly(0) => write!(f, "no arguments"),
+            Arity::Exactly(n) => write!(f, "{} argument{}", n, plural(n)),
+            Arity::AtLeast(0) => write!(f, "any number of arguments"),
+            Arity::AtLeast(n) => write!(f, "{} or more arguments", n),
+            Arity::Operands(n) => write!(f, "{} operand{}", n, plural(n)),
+        }
+    }
+}
+
+const TOPICS: &[Topic] = &[
+    Topic {
+        name: "sin",
+        usage: "sin(x)",
+        arity: Arity::Exactly(1),
+        description: "Sine of x, in the current angle mode",
+        example: "sin(pi / 2) is 1",
+    },
+    Topic {
+        name: "cos",
+        usage: "cos(x)",
+        arity: Arity::Exactly(1),
+        description: "Cosine of x, in the current angle mode",
+        example: "cos(0) is 1",
+    },
+    Topic {
+        name: "tan",
+        usage: "tan(x)",
+        arity: Arity::Exactly(1),
+        description: "Tangent of x, in the current angle mode",
+        example: "tan(0) is 0",
+    },
+    Topic {
+        name: "asin",
+        usage: "asin(x)",
+        arity: Arity::Exactly(1),
+        description: "Angle whose sine is x, in the current angle mode",
+        example: "asin(1) is 90 in degrees",
+    },
+    Topic {
+        name: "acos",
+        usage: "acos(x)",
+        arity: Arity::Exactly(1),
+        description: "Angle whose cosine is x, in the current angle mode",
+        example: "acos(1) is 0",
+    },
+    Topic {
+        name: "atan",
+        usage: "atan(x)",
+        arity: Arity::Exactly(1),
+        description: "Angle whose tangent is x, in the current angle mode",
+        example: "atan(1) is 45 in degrees",
+    },
+    Topic {
+        name: "atan2",
+        usage: "atan2(y, x)",
+        arity: Arity::Exactly(2),
+        description: "Angle of the point (x, y), correct in every quadrant",
+        example: "atan2(1, -1) is 135 in degrees",
+    },
+    Topic {
+        name: "sqrt",
+        usage: "sqrt(x)",
+        arity: Arity::Exactly(1),
+        description: "Square root; negative x is an error unless in mode complex",
+        example: "sqrt(16) is 4",
+    },
+    Topic {
+        name: "abs",
+        usage: "abs(x)",
+        arity: Arity::Exactly(1),
+        description: "Absolute value, or the modulus of a complex number; also |x|",
+        example: "abs(-5) is 5",
+    },
+    Topic {
+        name: "conj",
+        usage: "conj(z)",
+        arity: Arity::Exactly(1),
+        description: "Complex conjugate, in mode complex",
+        example: "conj(3+4i) is 3-4i",
+    },
+    Topic {
+        name: "exp",
+        usage: "exp(x)",
+        arity: Arity::Exactly(1),
+        description: "e raised to the power x",
+        example: "exp(0) is 1",
+    },
+    Topic {
+        name: "ln",
+        usage: "ln(x)",
+        arity: Arity::Exactly(1),
+        description: "Natural logarithm of a positive number",
+        example: "ln(e) is 1",
+    },
+    Topic {
+        name: "log",
+        usage: "log(x)",
+        arity: Arity::Exactly(1),
+        description: "Base-10 logarithm of a positive number",
+        example: "log(1000) is 3",
+    },
+    Topic {
+        name: "not",
+        usage: "not(x)",
+        arity: Arity::Exactly(1),
+        description: "True if x is zero, false otherwise",
+        example: "not(0) is 1",
+    },
+    Topic {
+        name: "round_to",
+        usage: "round_to(x, step)",
+        arity: Arity::Exactly(2),
+        description: "Nearest multiple of step",
+        example: "round_to(7.3, 0.25) is 7.25",
+    },
+    Topic {
+        name: "pctchange",
+        usage: "pctchange(old, new)",
+        arity: Arity::Exactly(2),
+        description: "Percentage change from old to new",
+        example: "pctchange(50, 75) is 50",
+    },
+    Topic {
+        name: "mean",
+        usage: "mean(x, ...)",
+        arity: Arity::AtLeast(1),
+        description: "Arithmetic mean; a list counts as its elements",
+        example: "mean(1, 2, 6) is 3",
+    },
+    Topic {
+        name: "intpart",
+        usage: "intpart(x)",
+        arity: Arity::Exactly(1),
+        description: "x with its fraction dropped, toward zero",
+        example: "intpart(-1.25) is -1",
+    },
+    Topic {
+        name: "fracpart",
+        usage: "fracpart(x)",
+        arity: Arity::Exactly(1),
+        description: "x - intpart(x), with the sign of x",
+        example: "fracpart(-1.25) is -0.25",
+    },
+    Topic {
+        name: "saturate",
+        usage: "saturate(x)",
+        arity: Arity::Exactly(1),
+        description: "x clamped to the range 0 to 1",
+        example: "saturate(1.5) is 1",
+    },
+    Topic {
+        name: "lerp",
+        usage: "lerp(a, b, t)",
+        arity: Arity::Exactly(3),
+        description: "Linear interpolation, a + (b - a) * t",
+        example: "lerp(0, 10, 0.25) is 2.5",
+    },
+    Topic {
+        name: "sum",
+        usage: "sum(x, ...)",
+        arity: Arity::AtLeast(0),
+        description: "Total of the values, or of a list's elements",
+        example: "sum(1, 2, 3) is 6",
+    },
+    Topic {
+        name: "max",
+        usage: "max(x, ...)",
+        arity: Arity::AtLeast(1),
+        description: "Largest of the values, or of a list's elements; also min",
+        example: "max(3, 9, 4) is 9",
+    },
+    Topic {
+        name: "map",
+        usage: "map(list, expression)",
+        arity: Arity::Exactly(2),
+        description: "A new list of the expression at x = each element",
+        example: "map(data, x^2) is 1, 4, 9 when data = 1, 2, 3",
+    },
+    Topic {
+        name: "median",
+        usage: "median(x, ...)",
+        arity: Arity::AtLeast(1),
+        description: "Middle value, or the mean of the two middle values",
+        example: "median(3, 1, 4, 2) is 2.5",
+    },
+    Topic {
+        name: "stddev",
+        usage: "stddev(x, ...)",
+        arity: Arity::AtLeast(2),
+        description: "Sample standard deviation, dividing by n - 1; also stddevs",
+        example: "stddev(2, 4, 4, 4, 5, 5, 7, 9) is 2.138...",
+    },
+    Topic {
+        name: "stddevp",
+        usage: "stddevp(x, ...)",
+        arity: Arity::AtLeast(1),
+        description: "Population standard deviation, dividing by n",
+        example: "stddevp(2, 4, 4, 4, 5, 5, 7, 9) is 2",
+    },
+    Topic {
+        name: "isqrt",
+        usage: "isqrt(n)",
+        arity: Arity::Exactly(1),
+        description: "Integer square root of a whole number, rounded down",
+        example: "isqrt(17) is 4",
+    },
+    Topic {
+        name: "isperfectsquare",
+        usage: "isperfectsquare(n)",
+        arity: Arity::Exactly(1),
+        description: "1 if n is the square of a whole number, 0 otherwise",
+        example: "isperfectsquare(49) is 1",
+    },
+    Topic {
+        name: "random",
+        usage: "random()",
+        arity: Arity::Exactly(0),
+        description: "Uniform random number in [0, 1); see seed",
+        example: "random() might be 0.405...",
+    },
+    Topic {
+        name: "bitand",
+        usage: "bitand(a, b)",
+        arity: Arity::Exactly(2),
+        description: "Bitwise AND of two integers",
+        example: "bitand(12, 10) is 8",
+    },
+    Topic {
+        name: "bitor",
+        usage: "bitor(a, b)",
+        arity: Arity::Exactly(2),
+        description: "Bitwise OR of two integers",
+        example: "bitor(12, 10) is 14",
+    },
+    Topic {
+        name: "bitxor",
+        usage: "bitxor(a, b)",
+        arity: Arity::Exactly(2),
+        description: "Bitwise exclusive OR of two integers",
+        example: "bitxor(12, 10) is 6",
+    },
+    Topic {
+        name: "bitnot",
+        usage: "bitnot(a)",
+        arity: Arity::Exactly(1),
+        description: "Bitwise complement of an integer",
+        example: "bitnot(0) is -1",
+    },
+    Topic {
+        name: "derivative",
+        usage: "derivative(expr, x)",
+        arity: Arity::Exactly(2),
+        description: "The derivative of expr with respect to x, worked out symbolically",
+        example: "derivative(x^2 + 3*x, x) is 2*x + 3",
+    },
+    Topic {
+        name: "add",
+        usage: "add(a, b)",
+        arity: Arity::Exactly(2),
+        description: "a + b as a function; also sub, mul and div",
+        example: "add(2, mul(3, 4)) is 14",
+    },
+    Topic {
+        name: "pi",
+        usage: "pi or pi()",
+        arity: Arity::Exactly(0),
+        description: "The constant 3.14159...; also e, inf, true and false",
+        example: "2 * pi() is 6.283...",
+    },
+    Topic {
+        name: "diff",
+        usage: "diff(expr, x, at)",
+        arity: Arity::Exactly(3),
+        description: "The derivative of expr with respect to x at x = at, worked out numerically",
+        example: "diff(x^2, x, 3) is 6",
+    },
+    Topic {
+        name: "solve",
+        usage: "solve(lhs = rhs, x)",
+        arity: Arity::Exactly(2),
+        description: "The x that makes a linear equation hold",
+        example: "solve(2*x + 1 = 7, x) is 3",
+    },
+    Topic {
+        name: "defined",
+        usage: "defined(name)",
+        arity: Arity::Exactly(1),
+        description: "1 if name is a variable or constant, without evaluating it",
+        example: "defined(pi) is 1",
+    },
+    Topic {
+        name: "+",
+        usage: "a + b",
+        arity: Arity::Operands(2),
+        description: "Addition; prefix + changes nothing",
+        example: "2 + 3 is 5",
+    },
+    Topic {
+        name: "-",
+        usage: "a - b",
+        arity: Arity::Operands(2),
+        description: "Subtraction; prefix - negates",
+        example: "2 - 3 is -1",
+    },
+    Topic {
+        name: "*",
+        usage: "a * b",
+        arity: Arity::Operands(2),
+        description: "Multiplication",
+        example: "2 * 3 is 6",
+    },
+    Topic {
+        name: "/",
+        usage: "a / b",
+        arity: Arity::Operands(2),
+        description: "Division; exact fractions in mode frac",
+        example: "7 / 2 is 3.5",
+    },
+    Topic {
+        name: "//",
+        usage: "a // b",
+        arity: Arity::Operands(2),
+        description: "Floor division, rounding toward negative infinity",
+        example: "-7 // 2 is -4",
+    },
+    Topic {
+        name: "mod",
+        usage: "a mod b",
+        arity: Arity::Operands(2),
+        description: "Remainder of floor division, with the sign of b",
+        example: "-7 mod 3 is 2",
+    },
+    Topic {
+        name: "^",
+        usage: "a ^ b",
+        arity: Arity::Operands(2),
+        description: "Power, grouping right to left; also ** and superscripts such as x²",
+        example: "2 ^ 3 ^ 2 is 512",
+    },
+    Topic {
+        name: "%",
+        usage: "a%",
+        arity: Arity::Operands(1),
+        description: "Percent, a / 100; in mode finance, a + b% adds b% of a",
+        example: "200 * 10% is 20",
+    },
+    Topic {
+        name: "°",
+        usage: "a°",
+        arity: Arity::Operands(1),
+        description: "An angle in degrees, whatever the angle mode",
+        example: "sin(90°) is 1",
+    },
+    Topic {
+        name: "<<",
+        usage: "a << b",
+        arity: Arity::Operands(2),
+        description: "Integer a shifted left b bits, an error if bits are lost; also >>",
+        example: "3 << 4 is 48",
+    },
+    Topic {
+        name: "==",
+        usage: "a == b",
+        arity: Arity::Operands(2),
+        description: "Equality; also !=, <, <=, > and >=",
+        example: "1 + 1 == 2 is 1",
+    },
+    Topic {
+        name: "~=",
+        usage: "a ~= b",
+        arity: Arity::Operands(2),
+        description: "Equal within the session's tolerance",
+        example: "0.1 + 0.2 ~= 0.3 is 1",
+    },
+    Topic {
+        name: "and",
+        usage: "a and b",
+        arity: Arity::Operands(2),
+        description: "True if both are nonzero; also or",
+        example: "1 and 0 is 0",
+    },
+];
+
+// `help`: list every topic; `help <name>`: describe one
+pub(crate) fn help(name: Option<&str>) -> Result<String, String> {
+    let Some(name) = name else {
+        let names: Vec<&str> = TOPICS.iter().map(|topic| topic.name).collect();
+        return Ok(format!(
+            "Functions and operators: {}\nUse help <name> for details",
+            names.join(" ")
+        ));
+    };
+
+    let topic = find(name).ok_or_else(|| format!("No help for '{}'", name))?;
+    Ok(format!(
+        "{} ({})\n{}\nExample: {}",
+        topic.usage, topic.arity, topic.description, topic.example
+    ))
+}
+
+// Check a call to a built-in function against its entry, before evaluating it
+pub(crate) fn check_call(name: &str, count: usize) -> Result<(), String> {
+    let arity = match find(name) {
+        Some(topic) if !matches!(topic.arity, Arity::Operands(_)) => topic.arity,
+        _ => return Err(format!("Unknown function '{}'", name)),
+    };
+    match arity {
+        Arity::Exactly(n) if count != n => Err(format!("Function '{}' expects {}", name, arity)),
+        Arity::AtLeast(n) if count < n => Err(format!(
+            "Function '{}' expects at least {} argument{}",
+            name,
+            n,
+            if n == 1 { "" } else { "s" }
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn find(name: &str) -> Option<&'static Topic> {
+    // The names that share an entry
+    let name = match name {
+        "**" => "^",
+        "!=" | "<" | "<=" | ">" | ">=" => "==",
+        "or" => "and",
+        ">>" => "<<",
+        "stddevs" => "stddev",
+        "min" => "max",
+        "sub" | "mul" | "div" => "add",
+        "e" | "inf" | "true" | "false" => "pi",
+        _ => name,
+    };
+    TOPICS.iter().find(|topic| topic.name == name)
+}
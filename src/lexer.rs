@@ -0,0 +1,455 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::session::{DisplayMode, Session};
+
+/// Tokens produced by [`tokenize`] and rearranged into RPN by [`parse`](crate::parse)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    // A literal written without a decimal point or suffix that fits in an i64
+    Integer(i64),
+    Number(f64),
+    // An imaginary literal such as `4i`, in `mode complex`
+    Imaginary(f64),
+    Identifier(String),
+    Operator(String),
+    LeftParenthesis,
+    RightParenthesis,
+    Comma,
+    // Postfix operator applied to the value before it, e.g. `90°` or `10%`
+    Postfix(char),
+    // Produced by the parser: prefix `-`
+    UnaryMinus,
+    // Produced by the parser: `defined(name)`, which tests the name itself
+    Defined(String),
+    // Produced by the parser: a call with its argument count
+    Function(String, usize),
+}
+
+/// Split an expression into tokens.
+///
+/// Square brackets group just like parentheses and produce the same tokens,
+/// but each closing bracket must match the kind that opened it. A `|` where
+/// a value is expected opens an absolute value and any other `|` closes one.
+///
+/// ```
+/// use calc::{tokenize, Token};
+///
+/// let tokens = tokenize("2 * (x + 1)").unwrap();
+/// assert_eq!(tokens[0], Token::Integer(2));
+/// assert_eq!(tokens[1], Token::Operator("*".to_string()));
+/// assert_eq!(tokens[3], Token::Identifier("x".to_string()));
+///
+/// assert_eq!(tokenize("[2 + 3] * [4 - 1]"), tokenize("(2 + 3) * (4 - 1)"));
+/// assert!(tokenize("[2 + 3)").is_err());
+///
+/// // Bars are absolute value
+/// assert_eq!(tokenize("|3 - 7|"), tokenize("abs(3 - 7)"));
+/// assert_eq!(tokenize("||1| - |2||"), tokenize("abs(abs(1) - abs(2))"));
+/// assert!(tokenize("|1| |2|").is_err());
+///
+/// // Any Unicode whitespace separates tokens, such as a tab or the
+/// // non-breaking space a copied number may carry
+/// assert_eq!(tokenize("2\t+\u{a0}3\u{3000}"), tokenize("2 + 3"));
+/// ```
+pub fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    tokenize_with(expression, &Session::new())
+}
+
+// Tokenize using the session's input modes
+pub(crate) fn tokenize_with(expression: &str, session: &Session) -> Result<Vec<Token>, String> {
+    tokenize_spanned(expression, session).map(|(tokens, _)| tokens)
+}
+
+// Tokenize, also returning the character offset each token starts at
+pub(crate) fn tokenize_spanned(
+    expression: &str,
+    session: &Session,
+) -> Result<(Vec<Token>, Vec<usize>), String> {
+    let mut tokens = Vec::new();
+    let mut positions = Vec::new();
+    let mut chars = Cursor::new(expression);
+    let mut start = 0;
+    // Kinds of the brackets still open, to catch `[2 + 3)`
+    let mut open = Vec::new();
+
+    while let Some(c) = chars.next() {
+        // Tokens pushed by the previous character all start where it did
+        positions.resize(tokens.len(), start);
+        start = chars.position - 1;
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                chars.next();
+                tokens.push(Token::Operator("//".to_string()));
+            }
+            // Python-style power, read as `^`
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                tokens.push(Token::Operator("^".to_string()));
+            }
+            '+' | '-' | '*' | '/' | '^' => tokens.push(Token::Operator(c.to_string())),
+            // Approximately equal, within the session's tolerance
+            '~' if chars.peek() == Some(&'=') => {
+                chars.next();
+                tokens.push(Token::Operator("~=".to_string()));
+            }
+            // Bit shifts of integers
+            '<' | '>' if chars.peek() == Some(&c) => {
+                chars.next();
+                tokens.push(Token::Operator(format!("{}{}", c, c)));
+            }
+            '<' | '>' | '=' | '!' => {
+                let op = if chars.peek() == Some(&'=') {
+                    chars.next();
+                    format!("{}=", c)
+                } else {
+                    c.to_string()
+                };
+                // A lone `=` is assignment and `!` has no meaning of its own
+                if op == "=" || op == "!" {
+                    return Err("Invalid character in expression".to_string());
+                }
+                tokens.push(Token::Operator(op));
+            }
+            '(' | '[' => {
+                // `mode strict-paren` explains this one below
+                if let Some(Token::Number(_) | Token::Integer(_)) = tokens.last() {
+                    if !session.strict_paren {
+                        return Err("Invalid expression format".to_string());
+                    }
+                }
+                open.push(c);
+                tokens.push(Token::LeftParenthesis);
+            }
+            ')' | ']' => {
+                // An unmatched close is left for the parser to report
+                let expected = if c == ')' { '(' } else { '[' };
+                match open.pop() {
+                    Some(opened) if opened != expected => {
+                        return Err(format!(
+                            "Mismatched brackets: '{}' closed by '{}'",
+                            opened, c
+                        ));
+                    }
+                    _ => tokens.push(Token::RightParenthesis),
+                }
+            }
+            // `|x|` is `abs(x)`. A bar where a value is expected opens, and
+            // anywhere else closes, so `||a| - |b||` nests as it reads.
+            '|' => {
+                let opens = matches!(
+                    tokens.last(),
+                    None | Some(Token::Operator(_) | Token::LeftParenthesis | Token::Comma)
+                );
+                if opens {
+                    open.push(c);
+                    tokens.push(Token::Identifier("abs".to_string()));
+                    tokens.push(Token::LeftParenthesis);
+                } else {
+                    match open.pop() {
+                        Some('|') => tokens.push(Token::RightParenthesis),
+                        Some(opened) => {
+                            return Err(format!("Mismatched brackets: '{}' closed by '|'", opened));
+                        }
+                        None => return Err("Unmatched '|'".to_string()),
+                    }
+                }
+            }
+            ',' => tokens.push(Token::Comma),
+            '°' | '%' => tokens.push(Token::Postfix(c)),
+            // `2²`, `x³`, `10⁻³`: a run of superscript digits is an exponent
+            c if superscript_digit(c).is_some() || c == '⁻' => {
+                let mut digits = String::new();
+                if let Some(digit) = superscript_digit(c) {
+                    digits.push(digit);
+                }
+                while let Some(digit) = chars.peek().copied().and_then(superscript_digit) {
+                    digits.push(digit);
+                    chars.next();
+                }
+                let exponent: i64 = digits
+                    .parse()
+                    .map_err(|_| "Invalid superscript exponent".to_string())?;
+                tokens.push(Token::Operator("^".to_string()));
+                tokens.push(Token::Integer(if c == '⁻' {
+                    -exponent
+                } else {
+                    exponent
+                }));
+            }
+            '0'..='9' | '.' => {
+                let mut buffer = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_ascii_digit() || next == '.') {
+                        break;
+                    }
+                    buffer.push(next);
+                    chars.next();
+                }
+                if session.time_mode && chars.peek() == Some(&':') {
+                    tokens.push(read_time(&mut chars, buffer)?);
+                    continue;
+                }
+                read_exponent(&mut chars, &mut buffer);
+                let mut scale = 1.0;
+                if session.display_mode == DisplayMode::Engineering {
+                    if let Some(suffix_scale) = read_si_suffix(&mut chars, &mut buffer) {
+                        scale = suffix_scale;
+                    }
+                }
+                if session.complex_mode && chars.peek() == Some(&'i') {
+                    let mut lookahead = chars.clone();
+                    lookahead.next();
+                    if !lookahead
+                        .peek()
+                        .is_some_and(|&next| next.is_ascii_alphanumeric() || next == '_')
+                    {
+                        chars.next();
+                        let number: f64 = buffer
+                            .parse()
+                            .map_err(|_| format!("Invalid number '{}'", buffer))?;
+                        tokens.push(Token::Imaginary(number * scale));
+                        continue;
+                    }
+                }
+                if scale == 1.0 && buffer.chars().all(|c| c.is_ascii_digit()) {
+                    if let Ok(integer) = buffer.parse() {
+                        tokens.push(Token::Integer(integer));
+                        continue;
+                    }
+                }
+                let number: f64 = buffer
+                    .parse()
+                    .map_err(|_| format!("Invalid number '{}'", buffer))?;
+                tokens.push(Token::Number(number * scale));
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let mut name = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_ascii_alphanumeric() || next == '_') {
+                        break;
+                    }
+                    name.push(next);
+                    chars.next();
+                }
+                match name.as_str() {
+                    // `%` is percent, so modulo is spelled out
+                    "and" | "or" | "mod" => tokens.push(Token::Operator(name)),
+                    _ => tokens.push(Token::Identifier(name)),
+                }
+            }
+            c if c.is_whitespace() => continue,
+            c if session.operators.contains_key(&c) => tokens.push(Token::Operator(c.to_string())),
+            _ => return Err("Invalid character in expression".to_string()),
+        }
+    }
+
+    // Unclosed parentheses are the parser's to report, but it would call
+    // an unclosed bar an unclosed '('
+    if open.contains(&'|') && !session.auto_close {
+        return Err("Unmatched '|'".to_string());
+    }
+
+    positions.resize(tokens.len(), start);
+    if session.strict_paren {
+        check_adjacent_values(&tokens, &positions)?;
+    }
+    Ok((tokens, positions))
+}
+
+// `mode strict-paren`: two values side by side, as in `2(3)`, `2pi` or
+// `(1)(2)`, are missing the `*` between them. A name before `(` is a call.
+fn check_adjacent_values(tokens: &[Token], positions: &[usize]) -> Result<(), String> {
+    for (index, pair) in tokens.windows(2).enumerate() {
+        let ends_value = matches!(
+            pair[0],
+            Token::Number(_)
+                | Token::Integer(_)
+                | Token::Imaginary(_)
+                | Token::Identifier(_)
+                | Token::RightParenthesis
+                | Token::Postfix(_)
+        );
+        let starts_value = match pair[1] {
+            Token::LeftParenthesis => !matches!(pair[0], Token::Identifier(_)),
+            Token::Number(_) | Token::Integer(_) | Token::Imaginary(_) | Token::Identifier(_) => {
+                true
+            }
+            _ => false,
+        };
+        if ends_value && starts_value {
+            return Err(format!(
+                "Missing '*' before position {}",
+                positions[index + 1] + 1
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Characters of the input, counting how many have been consumed
+#[derive(Clone)]
+struct Cursor<'a> {
+    chars: Peekable<Chars<'a>>,
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(text: &'a str) -> Self {
+        Cursor {
+            chars: text.chars().peekable(),
+            position: 0,
+        }
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        self.chars.peek()
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let next = self.chars.next()?;
+        self.position += 1;
+        Some(next)
+    }
+}
+
+// Consume a scientific-notation exponent (`e300`, `E-5`) into the buffer. An
+// `e` not followed by digits is left for the identifier it starts.
+fn read_exponent(chars: &mut Cursor, buffer: &mut String) {
+    let mut lookahead = chars.clone();
+    if !matches!(lookahead.next(), Some('e' | 'E')) {
+        return;
+    }
+    let sign = match lookahead.peek() {
+        Some(&c @ ('+' | '-')) => {
+            lookahead.next();
+            Some(c)
+        }
+        _ => None,
+    };
+    if !lookahead.peek().is_some_and(char::is_ascii_digit) {
+        return;
+    }
+
+    buffer.push(chars.next().unwrap());
+    if let Some(sign) = sign {
+        buffer.push(sign);
+        chars.next();
+    }
+    while let Some(&digit) = chars.peek() {
+        if !digit.is_ascii_digit() {
+            break;
+        }
+        buffer.push(digit);
+        chars.next();
+    }
+}
+
+// Consume a metric suffix directly after a number (`4.7k`, `10m`, `2M`) and
+// return its scale. Electronics-style `4k7` is accepted too: the suffix takes
+// the place of the decimal point, so the trailing digits are appended to the
+// buffer as the fraction. A letter followed by more identifier characters
+// (`10min`) is left alone.
+fn read_si_suffix(chars: &mut Cursor, buffer: &mut String) -> Option<f64> {
+    let scale = match chars.peek()? {
+        'p' => 1e-12,
+        'n' => 1e-9,
+        'u' => 1e-6,
+        'm' => 1e-3,
+        'k' => 1e3,
+        'M' => 1e6,
+        'G' => 1e9,
+        'T' => 1e12,
+        _ => return None,
+    };
+
+    let mut lookahead = chars.clone();
+    lookahead.next();
+    match lookahead.peek() {
+        Some(next) if next.is_ascii_digit() && !buffer.contains('.') => {
+            chars.next();
+            buffer.push('.');
+            while let Some(&digit) = chars.peek() {
+                if !digit.is_ascii_digit() {
+                    break;
+                }
+                buffer.push(digit);
+                chars.next();
+            }
+        }
+        Some(next) if next.is_ascii_alphanumeric() || *next == '_' => return None,
+        _ => {
+            chars.next();
+        }
+    }
+
+    Some(scale)
+}
+
+// Consume the rest of an `H:M` or `H:M:S` time after its hours and return
+// it in minutes. Minutes and seconds must be below 60; only the last part
+// may have a fraction.
+fn read_time(chars: &mut Cursor, hours: String) -> Result<Token, String> {
+    let mut fields = vec![hours];
+    while chars.peek() == Some(&':') {
+        chars.next();
+        let mut field = String::new();
+        while let Some(&next) = chars.peek() {
+            if !(next.is_ascii_digit() || next == '.') {
+                break;
+            }
+            field.push(next);
+            chars.next();
+        }
+        fields.push(field);
+    }
+
+    let invalid = || format!("Invalid time '{}'", fields.join(":"));
+    if fields.len() > 3 {
+        return Err(invalid());
+    }
+    let mut values = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        if field.is_empty() || (index < fields.len() - 1 && field.contains('.')) {
+            return Err(invalid());
+        }
+        let value: f64 = field.parse().map_err(|_| invalid())?;
+        if index > 0 && value >= 60.0 {
+            return Err(invalid());
+        }
+        values.push(value);
+    }
+
+    let minutes = values[0] * 60.0 + values[1] + values.get(2).map_or(0.0, |s| s / 60.0);
+    if minutes.fract() == 0.0 && minutes <= i64::MAX as f64 {
+        Ok(Token::Integer(minutes as i64))
+    } else {
+        Ok(Token::Number(minutes))
+    }
+}
+
+// The ASCII digit a superscript digit stands for
+fn superscript_digit(c: char) -> Option<char> {
+    match c {
+        '⁰' => Some('0'),
+        '¹' => Some('1'),
+        '²' => Some('2'),
+        '³' => Some('3'),
+        '⁴'..='⁹' => char::from_digit(c as u32 - '⁴' as u32 + 4, 10),
+        _ => None,
+    }
+}
+
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,539 @@
+//! Expression evaluator behind the calculator CLI.
+//!
+//! The pipeline is exposed stage by stage for tooling: [`tokenize`] splits
+//! the input, [`parse`] reorders the tokens into RPN, and [`evaluate`] runs
+//! the whole thing in one go. Generated token streams can skip the text
+//! entirely with [`to_rpn`] and [`evaluate_rpn`], and [`compile`] parses an
+//! expression once for evaluating many times.
+
+mod algebra;
+mod cache;
+mod calculus;
+mod canon;
+mod commands;
+mod compiled;
+mod complex;
+mod config;
+mod decimal;
+mod eval;
+mod explain;
+mod format;
+mod functions;
+mod help;
+mod lexer;
+mod parser;
+mod random;
+mod ratio;
+mod session;
+mod table;
+mod units;
+mod value;
+
+pub use canon::canonical_check;
+pub use commands::{check_input, run_command};
+pub use compiled::{compile, CompiledExpr};
+pub use complex::Complex;
+pub use config::{apply_config, apply_setting};
+pub use format::{format_result, format_shortest, format_sigfigs};
+pub use lexer::{tokenize, Token};
+pub use session::{
+    check_statement, evaluate_statement, repeat_statement, AngleMode, Associativity, DisplayMode,
+    Profile, Session,
+};
+pub use value::Value;
+
+use std::time::Instant;
+
+use eval::{evaluate_tree, expression_cost, returns_angle};
+use lexer::tokenize_spanned;
+use parser::{parse_spanned, parse_tokens};
+
+/// Evaluate an expression with default settings.
+///
+/// ```
+/// assert_eq!(calc::evaluate("2 + 3 * 4"), Ok(14.0));
+/// assert_eq!(calc::evaluate("+5"), Ok(5.0));
+/// assert_eq!(calc::evaluate("3 * +2"), Ok(6.0));
+/// assert_eq!(calc::evaluate("-+3"), Ok(-3.0));
+/// assert!(calc::evaluate("-0.0").unwrap().is_sign_positive());
+/// assert_eq!(calc::evaluate("10 mod 3"), Ok(1.0));
+/// assert_eq!(calc::evaluate("1 + 10 mod 3 + 1"), Ok(3.0));
+/// assert!(calc::evaluate("10 mod 0").is_err());
+/// assert_eq!(calc::evaluate("intpart(3.75)"), Ok(3.0));
+/// assert_eq!(calc::evaluate("fracpart(3.75)"), Ok(0.75));
+/// // Both parts keep the sign of the input
+/// assert_eq!(calc::evaluate("intpart(-1.25)"), Ok(-1.0));
+/// assert_eq!(calc::evaluate("fracpart(-1.25)"), Ok(-0.25));
+/// assert_eq!(calc::evaluate("saturate(-0.5)"), Ok(0.0));
+/// assert_eq!(calc::evaluate("saturate(2)"), Ok(1.0));
+/// assert_eq!(calc::evaluate("lerp(0, 10, 0.25)"), Ok(2.5));
+/// assert_eq!(calc::evaluate("round_to(7.3, 0.25)"), Ok(7.25));
+/// assert_eq!(calc::evaluate("round_to(-7.3, 5)"), Ok(-5.0));
+/// assert_eq!(
+///     calc::evaluate("round_to(7.3, 0)"),
+///     Err("Rounding increment cannot be zero".to_string())
+/// );
+/// assert!(calc::evaluate("round_to(7.3)").is_err());
+/// // Constants can be called with no arguments, like `random()`
+/// assert_eq!(calc::evaluate("pi()"), Ok(std::f64::consts::PI));
+/// assert_eq!(calc::evaluate("2 * e()"), calc::evaluate("2 * e"));
+/// assert!(calc::evaluate("pi(1)").is_err());
+/// assert_eq!(
+///     calc::evaluate("sqrt()"),
+///     Err("Function 'sqrt' expects 1 argument".to_string())
+/// );
+/// // Calls are checked against the same table `help` describes
+/// assert_eq!(
+///     calc::evaluate("frobnicate(1)"),
+///     Err("Unknown function 'frobnicate'".to_string())
+/// );
+/// assert_eq!(
+///     calc::evaluate("lerp(1, 2)"),
+///     Err("Function 'lerp' expects 3 arguments".to_string())
+/// );
+/// assert_eq!(
+///     calc::evaluate("stddev(1)"),
+///     Err("Function 'stddev' expects at least 2 arguments".to_string())
+/// );
+/// assert_eq!(calc::evaluate("add(2, mul(3, 4))"), Ok(14.0));
+/// assert_eq!(calc::evaluate("sub(1, div(1, 4))"), Ok(0.75));
+/// assert_eq!(calc::evaluate("div(1, 0)"), Err("Division by zero".to_string()));
+/// assert!(calc::evaluate("add(1)").is_err());
+/// assert_eq!(calc::evaluate("median(3, 1, 2)"), Ok(2.0));
+/// // An even count averages the two middle values
+/// assert_eq!(calc::evaluate("median(3, 1, 4, 2)"), Ok(2.5));
+/// assert_eq!(calc::evaluate("mean(1, 2, 6)"), Ok(3.0));
+/// assert_eq!(calc::evaluate("stddevp(2, 4, 4, 4, 5, 5, 7, 9)"), Ok(2.0));
+/// assert!(calc::evaluate("stddev(5)").is_err());
+/// assert!(calc::evaluate("median()").is_err());
+/// assert_eq!(calc::evaluate("pctchange(50, 75)"), Ok(50.0));
+/// assert_eq!(calc::evaluate("pctchange(80, 60)"), Ok(-25.0));
+/// assert_eq!(
+///     calc::evaluate("pctchange(0, 5)"),
+///     Err("Percentage change from zero is undefined".to_string())
+/// );
+/// assert_eq!(calc::evaluate("1 << 10 >> 2"), Ok(256.0));
+/// // `**` is `^`, grouping right to left and binding tighter than prefix -
+/// assert_eq!(calc::evaluate("2**3**2"), Ok(512.0));
+/// assert_eq!(calc::evaluate("2**3**2"), calc::evaluate("2^3^2"));
+/// assert_eq!(calc::evaluate("-2**2"), Ok(-4.0));
+/// assert!(calc::evaluate("2***3").is_err());
+/// // Superscript digits are an exponent
+/// assert_eq!(calc::evaluate("2¹²"), Ok(4096.0));
+/// assert_eq!(calc::evaluate("(1 + 2)²"), Ok(9.0));
+/// assert_eq!(calc::evaluate("-3²"), Ok(-9.0));
+/// assert_eq!(calc::evaluate("1 << 70"), Err("Integer overflow".to_string()));
+/// // Floats with no fractional part can be shifted, as with bitand
+/// assert_eq!(calc::evaluate("8.0 << 1"), Ok(16.0));
+/// assert_eq!(calc::evaluate("sqrt(16) >> 1"), Ok(2.0));
+/// assert!(calc::evaluate("2.5 << 1").is_err());
+/// assert_eq!(calc::evaluate("bitand(12, 10)"), Ok(8.0));
+/// assert_eq!(calc::evaluate("bitor(12, 10)"), Ok(14.0));
+/// assert_eq!(calc::evaluate("bitxor(12, 10)"), Ok(6.0));
+/// assert_eq!(calc::evaluate("bitnot(0)"), Ok(-1.0));
+/// assert_eq!(calc::evaluate("bitnot(bitnot(5))"), Ok(5.0));
+/// assert!(calc::evaluate("bitand(1.5, 1)").is_err());
+/// // Whole-number arithmetic that outgrows an i64 carries on as a float
+/// assert_eq!(calc::evaluate("9223372036854775807 * 2"), Ok(1.8446744073709552e19));
+///
+/// // Syntax errors say what was expected, what was found and where,
+/// // counting from 1
+/// assert_eq!(
+///     calc::evaluate("2 +"),
+///     Err("Expected a number or '(' but found end of input".to_string())
+/// );
+/// assert_eq!(
+///     calc::evaluate("2 3"),
+///     Err("Expected an operator or ')' but found '3' at position 3".to_string())
+/// );
+/// assert_eq!(
+///     calc::evaluate("1 + )"),
+///     Err("Expected a number or '(' but found ')' at position 5".to_string())
+/// );
+///
+/// for input in ["* 5", "(/ 2)", "2 + / 3"] {
+///     let operator = input.chars().find(|c| "*/".contains(*c)).unwrap();
+///     let expected = format!("Expected a value before operator '{}'", operator);
+///     assert_eq!(calc::evaluate(input), Err(expected));
+/// }
+/// ```
+pub fn evaluate(expression: &str) -> Result<f64, String> {
+    evaluate_expression(expression, &Session::new()).map(|value| value.as_f64())
+}
+
+/// Evaluate an expression handed over as bytes, such as from a network
+/// buffer or across FFI, with default settings. The bytes must be UTF-8.
+///
+/// ```
+/// assert_eq!(calc::evaluate_bytes(b"2 + 3 * 4"), Ok(14.0));
+/// assert_eq!(
+///     calc::evaluate_bytes(b"2 + \xFF"),
+///     Err("Invalid UTF-8 at byte 4".to_string())
+/// );
+/// ```
+pub fn evaluate_bytes(input: &[u8]) -> Result<f64, CalcError> {
+    let expression = std::str::from_utf8(input)
+        .map_err(|error| format!("Invalid UTF-8 at byte {}", error.valid_up_to()))?;
+    evaluate(expression)
+}
+
+/// Reorder tokens into Reverse Polish Notation using the default precedence
+/// rules. Function calls become [`Token::Function`] carrying their argument
+/// count, prefix `-` becomes [`Token::UnaryMinus`] and `defined(name)`
+/// becomes [`Token::Defined`].
+///
+/// ```
+/// use calc::{parse, tokenize, Token};
+///
+/// let rpn = parse(tokenize("1 + 2 * 3").unwrap()).unwrap();
+/// assert_eq!(
+///     rpn,
+///     vec![
+///         Token::Integer(1),
+///         Token::Integer(2),
+///         Token::Integer(3),
+///         Token::Operator("*".to_string()),
+///         Token::Operator("+".to_string()),
+///     ]
+/// );
+/// ```
+pub fn parse(tokens: Vec<Token>) -> Result<Vec<Token>, String> {
+    parse_tokens(tokens, &Session::new())
+}
+
+/// Errors from the library functions: a message ready to show the user
+pub type CalcError = String;
+
+/// Reorder tokens into RPN, for callers that build token streams themselves
+/// rather than parsing text. The same as [`parse`].
+///
+/// ```
+/// use calc::{to_rpn, Token};
+///
+/// // (1 + 2) * 3
+/// let tokens = vec![
+///     Token::LeftParenthesis,
+///     Token::Integer(1),
+///     Token::Operator("+".to_string()),
+///     Token::Integer(2),
+///     Token::RightParenthesis,
+///     Token::Operator("*".to_string()),
+///     Token::Integer(3),
+/// ];
+/// let rpn = to_rpn(tokens).unwrap();
+/// assert_eq!(rpn[2], Token::Operator("+".to_string()));
+/// assert_eq!(rpn[4], Token::Operator("*".to_string()));
+/// ```
+pub fn to_rpn(tokens: Vec<Token>) -> Result<Vec<Token>, CalcError> {
+    parse(tokens)
+}
+
+/// Evaluate tokens already in RPN with default settings. There is no
+/// precedence to get wrong: each operator applies to the two values before
+/// it.
+///
+/// ```
+/// use calc::{evaluate_rpn, Token};
+///
+/// // 2 3 4 * +  is  2 + 3 * 4
+/// let rpn = [
+///     Token::Integer(2),
+///     Token::Integer(3),
+///     Token::Integer(4),
+///     Token::Operator("*".to_string()),
+///     Token::Operator("+".to_string()),
+/// ];
+/// assert_eq!(evaluate_rpn(&rpn), Ok(14.0));
+///
+/// let incomplete = [Token::Integer(2), Token::Operator("+".to_string())];
+/// assert!(evaluate_rpn(&incomplete).is_err());
+/// ```
+pub fn evaluate_rpn(tokens: &[Token]) -> Result<f64, CalcError> {
+    evaluate_tree(tokens, &Session::new()).map(|value| value.as_f64())
+}
+
+/// Evaluate an expression against a session's variables and modes
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let mut session = Session::new();
+/// assert_eq!(evaluate_expression("200 * 10%", &session), Ok(Value::Float(20.0)));
+/// assert_eq!(evaluate_expression("200 + 10%", &session), Ok(Value::Float(200.1)));
+///
+/// session.finance_mode = true;
+/// assert_eq!(evaluate_expression("200 + 10%", &session), Ok(Value::Float(220.0)));
+/// assert_eq!(evaluate_expression("200 * 10%", &session), Ok(Value::Float(20.0)));
+/// ```
+///
+/// Under `mode strict-paren`, values side by side are reported as a missing
+/// `*`:
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let mut session = Session::new();
+/// session.strict_paren = true;
+/// assert_eq!(
+///     evaluate_expression("2(3)", &session),
+///     Err("Missing '*' before position 2".to_string())
+/// );
+/// assert_eq!(evaluate_expression("2*(3)", &session), Ok(Value::Int(6)));
+/// ```
+///
+/// `mode autoclose` closes parentheses still open at the end:
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let mut session = Session::new();
+/// assert!(evaluate_expression("2 * (3 + (4", &session).is_err());
+/// session.auto_close = true;
+/// assert_eq!(evaluate_expression("2 * (3 + (4", &session), Ok(Value::Int(14)));
+/// assert_eq!(evaluate_expression("sqrt(16", &session), Ok(Value::Float(4.0)));
+/// // A stray closing parenthesis is still an error
+/// assert!(evaluate_expression("2 * 3)", &session).is_err());
+/// ```
+///
+/// `true` and `false` are literals, counting as 1 and 0 in arithmetic:
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let session = Session::new();
+/// assert_eq!(evaluate_expression("true and false", &session), Ok(Value::Bool(false)));
+/// assert_eq!(evaluate_expression("true or false", &session), Ok(Value::Bool(true)));
+/// assert_eq!(evaluate_expression("true == (1 < 2)", &session), Ok(Value::Bool(true)));
+/// assert_eq!(evaluate_expression("true + true", &session), Ok(Value::Int(2)));
+/// ```
+///
+/// `inf` compares and calculates as IEEE 754 says, unless `mode strict`
+/// makes a non-finite result an error:
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let mut session = Session::new();
+/// assert_eq!(evaluate_expression("inf > 1e308", &session), Ok(Value::Bool(true)));
+/// assert_eq!(evaluate_expression("-inf < -1e308", &session), Ok(Value::Bool(true)));
+/// assert_eq!(evaluate_expression("1 / inf", &session), Ok(Value::Float(0.0)));
+/// assert_eq!(evaluate_expression("1e308 * 10", &session), Ok(Value::Float(f64::INFINITY)));
+/// assert!(evaluate_expression("inf - inf", &session).unwrap().as_f64().is_nan());
+///
+/// session.strict_mode = true;
+/// let error = Err("Result is not a finite number".to_string());
+/// assert_eq!(evaluate_expression("1e308 * 10", &session), error);
+/// assert_eq!(evaluate_expression("inf - inf", &session), error);
+/// assert_eq!(evaluate_expression("inf > 1", &session), Ok(Value::Bool(true)));
+/// ```
+///
+/// `mode frac` keeps uneven division exact, in lowest terms:
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let mut session = Session::new();
+/// session.frac_mode = true;
+/// assert_eq!(evaluate_expression("6/8", &session), Ok(Value::Ratio(3, 4)));
+/// assert_eq!(evaluate_expression("1/3 + 1/6", &session), Ok(Value::Ratio(1, 2)));
+/// assert_eq!(evaluate_expression("-2/4", &session), Ok(Value::Ratio(-1, 2)));
+/// assert_eq!(evaluate_expression("3/4 * 4/3", &session), Ok(Value::Int(1)));
+/// assert_eq!(evaluate_expression("8/2", &session), Ok(Value::Int(4)));
+/// ```
+///
+/// `~=` is equality within the session's tolerance, relative to the size of
+/// the operands once they are above 1:
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let mut session = Session::new();
+/// assert_eq!(evaluate_expression("0.1 + 0.2 == 0.3", &session), Ok(Value::Bool(false)));
+/// assert_eq!(evaluate_expression("0.1 + 0.2 ~= 0.3", &session), Ok(Value::Bool(true)));
+/// assert_eq!(evaluate_expression("1 ~= 1.001", &session), Ok(Value::Bool(false)));
+/// assert_eq!(evaluate_expression("1e20 ~= 1e20 + 1e5", &session), Ok(Value::Bool(true)));
+/// session.tolerance = 0.01;
+/// assert_eq!(evaluate_expression("1 ~= 1.001", &session), Ok(Value::Bool(true)));
+/// ```
+///
+/// `defined(name)` checks a name without evaluating it:
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let mut session = Session::new();
+/// session.variables.insert("x".to_string(), Value::Int(1));
+/// assert_eq!(evaluate_expression("defined(x)", &session), Ok(Value::Bool(true)));
+/// assert_eq!(evaluate_expression("defined(y)", &session), Ok(Value::Bool(false)));
+/// ```
+///
+/// In `mode time`, `H:M` and `H:M:S` are durations counted in minutes:
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let mut session = Session::new();
+/// session.time_mode = true;
+/// assert_eq!(evaluate_expression("1:30 + 0:45", &session), Ok(Value::Int(135)));
+/// assert_eq!(evaluate_expression("1:30:30", &session), Ok(Value::Float(90.5)));
+/// assert_eq!(
+///     evaluate_expression("1:75", &session),
+///     Err("Invalid time '1:75'".to_string())
+/// );
+/// ```
+///
+/// Evaluation runs over a value stack rather than recursing, so a very long
+/// chain or deep nesting is limited only by the session's limits:
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let mut session = Session::new();
+/// session.max_input_length = usize::MAX;
+/// session.max_cost = usize::MAX;
+/// let chain = "1+".repeat(100_000) + "1";
+/// assert_eq!(evaluate_expression(&chain, &session), Ok(Value::Int(100_001)));
+/// let nested = format!("{}1{}", "(".repeat(100_000), "+1)".repeat(100_000));
+/// assert_eq!(evaluate_expression(&nested, &session), Ok(Value::Int(100_001)));
+/// ```
+///
+/// `mode simple` applies operators strictly left to right, as a pocket
+/// calculator does, where the default follows BODMAS:
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let mut session = Session::new();
+/// assert_eq!(evaluate_expression("2 + 3 * 4", &session), Ok(Value::Int(14)));
+/// assert_eq!(evaluate_expression("10 - 2 ^ 2", &session), Ok(Value::Int(6)));
+///
+/// session.simple_mode = true;
+/// assert_eq!(evaluate_expression("2 + 3 * 4", &session), Ok(Value::Int(20)));
+/// assert_eq!(evaluate_expression("10 - 2 ^ 2", &session), Ok(Value::Int(64)));
+/// assert_eq!(evaluate_expression("2 + (3 * 4)", &session), Ok(Value::Int(14)));
+/// ```
+///
+/// In engineering display, numbers take SI suffixes:
+///
+/// ```
+/// use calc::{evaluate_expression, DisplayMode, Session, Value};
+///
+/// let mut session = Session::new();
+/// assert!(evaluate_expression("4.7k", &session).is_err());
+///
+/// session.display_mode = DisplayMode::Engineering;
+/// assert_eq!(evaluate_expression("4.7k", &session), Ok(Value::Float(4700.0)));
+/// assert_eq!(evaluate_expression("10m", &session), Ok(Value::Float(0.01)));
+/// assert_eq!(evaluate_expression("2M", &session), Ok(Value::Float(2e6)));
+/// assert_eq!(evaluate_expression("3u * 1G", &session), Ok(Value::Float(3000.0)));
+/// ```
+///
+/// A postfix `°` marks an angle in degrees whatever the angle mode:
+///
+/// ```
+/// use calc::{evaluate_expression, AngleMode, Session, Value};
+///
+/// let mut session = Session::new();
+/// assert_eq!(evaluate_expression("sin(90°)", &session), Ok(Value::Float(1.0)));
+/// assert_eq!(evaluate_expression("cos(180°)", &session), Ok(Value::Float(-1.0)));
+/// session.angle_mode = AngleMode::Degrees;
+/// assert_eq!(evaluate_expression("sin(90°)", &session), Ok(Value::Float(1.0)));
+/// assert_eq!(evaluate_expression("sin(90)", &session), Ok(Value::Float(1.0)));
+/// ```
+///
+/// `atan2(y, x)` gives the angle of the point (x, y) in every quadrant, in
+/// the session's angle mode:
+///
+/// ```
+/// use calc::{evaluate_expression, AngleMode, Session};
+///
+/// let mut session = Session::new();
+/// let angle = |input, session: &Session| evaluate_expression(input, session).unwrap().as_f64();
+/// let close = |a: f64, b: f64| (a - b).abs() < 1e-12;
+/// let quarter = std::f64::consts::FRAC_PI_4;
+/// assert!(close(angle("atan2(1, 1)", &session), quarter));
+/// assert!(close(angle("atan2(1, -1)", &session), 3.0 * quarter));
+/// assert!(close(angle("atan2(-1, -1)", &session), -3.0 * quarter));
+/// assert!(close(angle("atan2(-1, 1)", &session), -quarter));
+///
+/// session.angle_mode = AngleMode::Degrees;
+/// assert!(close(angle("atan2(1, 1)", &session), 45.0));
+/// assert!(close(angle("atan2(1, -1)", &session), 135.0));
+/// assert!(close(angle("atan2(-1, -1)", &session), -135.0));
+/// assert!(close(angle("atan2(-1, 1)", &session), -45.0));
+/// assert!(close(angle("atan2(0, -1)", &session), 180.0));
+/// ```
+///
+/// `isqrt` and `isperfectsquare` work on the exact integer, however large:
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let session = Session::new();
+/// let n = "9223368999999999999";
+/// let isqrt = evaluate_expression(&format!("isqrt({})", n), &session);
+/// assert_eq!(isqrt, Ok(Value::Int(3036999999)));
+/// let square = evaluate_expression(&format!("isperfectsquare({})", n), &session);
+/// assert_eq!(square, Ok(Value::Int(0)));
+/// assert_eq!(evaluate_expression("isperfectsquare(49.0)", &session), Ok(Value::Int(1)));
+/// assert!(evaluate_expression("isqrt(-4)", &session).is_err());
+/// assert!(evaluate_expression("isqrt(2.5)", &session).is_err());
+/// ```
+pub fn evaluate_expression(expression: &str, session: &Session) -> Result<Value, String> {
+    check_length(expression, session)?;
+
+    let start = Instant::now();
+
+    // Tokenize the expression
+    let (tokens, positions) = tokenize_spanned(expression, session)?;
+    let tokenized = Instant::now();
+
+    // Parse the tokens into a syntax tree
+    let tree = parse_spanned(tokens, &positions, session)?;
+    let parsed = Instant::now();
+    check_cost(&tree, session)?;
+
+    // Evaluate the syntax tree
+    let result = evaluate_tree(&tree, session)?;
+    session.angle_result.set(returns_angle(&tree));
+    if session.profiling {
+        session.add_timings(tokenized - start, parsed - tokenized, parsed.elapsed());
+    }
+    let finite = match &result {
+        Value::Complex(z) => z.re.is_finite() && z.im.is_finite(),
+        Value::List(_) => true,
+        _ => result.as_f64().is_finite(),
+    };
+    if session.strict_mode && !finite {
+        return Err("Result is not a finite number".to_string());
+    }
+
+    Ok(result)
+}
+
+// Tokenize and parse within the session's limits, for callers that evaluate
+// the tree themselves or skip evaluation on a result cache hit
+pub(crate) fn parse_within_limits(
+    expression: &str,
+    session: &Session,
+) -> Result<Vec<Token>, String> {
+    check_length(expression, session)?;
+    let (tokens, positions) = tokenize_spanned(expression, session)?;
+    let tree = parse_spanned(tokens, &positions, session)?;
+    check_cost(&tree, session)?;
+    Ok(tree)
+}
+
+fn check_length(expression: &str, session: &Session) -> Result<(), String> {
+    if expression.chars().count() > session.max_input_length {
+        return Err("Expression too long".to_string());
+    }
+    Ok(())
+}
+
+fn check_cost(tree: &[Token], session: &Session) -> Result<(), String> {
+    if expression_cost(tree) > session.max_cost {
+        return Err("Expression too complex".to_string());
+    }
+    Ok(())
+}
@@ -1,176 +1,478 @@
-use std::io;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process;
 
-// Define token types
-#[derive(Debug)]
-enum Token {
-    Number(f64),
-    Operator(char),
-    LeftParenthesis,
-    RightParenthesis,
+use calc::{
+    apply_config, apply_setting, check_input, evaluate_statement, format_result, repeat_statement,
+    run_command, Session, Value,
+};
+
+// Read when no --config is given; a missing file is not an error
+const DEFAULT_CONFIG_FILE: &str = ".calcrc";
+
+// Environment variable holding the symbol for `mode currency`
+const CURRENCY_VARIABLE: &str = "CALC_CURRENCY";
+
+// ANSI colour codes for results and errors
+const GREEN: &str = "32";
+const RED: &str = "31";
+
+// Command-line flags
+struct Options {
+    // Suppress the banner, prompts and per-line output; print only the final result
+    print_last_only: bool,
+    // File that receives the final result, for shell/clipboard integration
+    out: Option<String>,
+    // File each statement and its result or error is appended to
+    log: Option<String>,
+    // Evaluate a single line from stdin and exit, without the REPL
+    stdin_once: bool,
+    // Check the syntax of every line on stdin without evaluating anything
+    check_only: bool,
+    // Show the banner and prompts even when stdin is not a terminal
+    interactive: bool,
+    // Skip the welcome line
+    no_banner: bool,
+    // Prompt with `> ` instead of the two-line hint
+    short_prompt: bool,
+    // Report time spent tokenizing, parsing and evaluating each input on stderr
+    profile: bool,
+    // Settings file overriding the built-in defaults
+    config: Option<String>,
+    // `key=value` settings from flags, which override the config file
+    settings: Vec<(String, String)>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        print_last_only: false,
+        out: None,
+        log: None,
+        stdin_once: false,
+        check_only: false,
+        interactive: false,
+        no_banner: false,
+        short_prompt: false,
+        profile: false,
+        config: None,
+        settings: Vec::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--print-last-only" => options.print_last_only = true,
+            "--stdin-once" => options.stdin_once = true,
+            "--check-only" => options.check_only = true,
+            "--interactive" => options.interactive = true,
+            "--no-banner" => options.no_banner = true,
+            "--short-prompt" => options.short_prompt = true,
+            "--profile" => options.profile = true,
+            "--out" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| "--out requires a file path".to_string())?;
+                options.out = Some(path);
+            }
+            "--log" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| "--log requires a file path".to_string())?;
+                options.log = Some(path);
+            }
+            "--config" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| "--config requires a file path".to_string())?;
+                options.config = Some(path);
+            }
+            "--precision" | "--angle" | "--base" | "--max-cost" | "--max-iterations"
+            | "--tolerance" | "--timeout" | "--seed" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{} requires a value", arg))?;
+                options
+                    .settings
+                    .push((arg.trim_start_matches("--").to_string(), value));
+            }
+            "--color" => options
+                .settings
+                .push(("color".to_string(), "on".to_string())),
+            "--no-color" => options
+                .settings
+                .push(("color".to_string(), "off".to_string())),
+            _ => return Err(format!("Unknown argument '{}'", arg)),
+        }
+    }
+
+    Ok(options)
 }
 
 fn main() {
-    println!("Welcome to the Rust Calculator CLI with BODMAS support!");
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            process::exit(2);
+        }
+    };
+    let quiet = options.print_last_only;
+
+    // Built-in defaults, then the config file, then command-line flags
+    let mut session = Session::new();
+    session.profiling = options.profile;
+    if let Ok(symbol) = env::var(CURRENCY_VARIABLE) {
+        session.currency_symbol = symbol;
+    }
+    if let Err(error) = load_config(&options, &mut session) {
+        eprintln!("Error: {}", error);
+        process::exit(2);
+    }
+    for (key, value) in &options.settings {
+        if let Err(error) = apply_setting(key, value, &mut session) {
+            eprintln!("Error: --{}: {}", key, error);
+            process::exit(2);
+        }
+    }
 
+    if options.stdin_once {
+        process::exit(run_stdin_once(&mut session, &options));
+    }
+    if options.check_only {
+        process::exit(run_check_only(&session));
+    }
+
+    let mut log = match open_log(&options) {
+        Ok(log) => log,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            process::exit(2);
+        }
+    };
+
+    // Piped input runs as a batch without the banner and prompts, unless
+    // --interactive asks for them (e.g. when driven by `expect`)
+    let batch = !(options.interactive || io::stdin().is_terminal());
+    let prompt = !quiet && !batch;
+
+    if prompt && !options.no_banner {
+        println!("Welcome to the Rust Calculator CLI with BODMAS support!");
+    }
+
+    // Each successful line is recorded before the next is read, so in a
+    // script `ans` on one line is the previous line's result (0 on the
+    // first), just as at the prompt. A line that fails leaves `ans` alone.
     loop {
-        println!("Enter an expression (e.g., 2 + 2) or type 'quit' to exit:");
+        if prompt && options.short_prompt {
+            print!("> ");
+            io::stdout().flush().expect("Failed to write prompt");
+        } else if prompt {
+            println!("Enter an expression (e.g., 2 + 2) or type 'quit' to exit:");
+        }
 
         let mut input = String::new();
-        io::stdin()
+        let bytes = io::stdin()
             .read_line(&mut input)
             .expect("Failed to read line");
+        if bytes == 0 {
+            break;
+        }
 
         // Trim leading and trailing whitespaces
         let input = input.trim();
 
         if input.to_lowercase() == "quit" {
-            println!("Goodbye!");
+            if prompt {
+                println!("Goodbye!");
+            }
             break;
         }
 
-        let result = evaluate_expression(input);
-        match result {
-            Ok(value) => println!("Result: {}", value),
-            Err(error) => println!("Error: {}", error),
+        // `.` or `!!` runs the last expression again
+        let recalled;
+        let input = if input == "." || input == "!!" {
+            match &session.last_input {
+                Some(last) => {
+                    recalled = last.clone();
+                    recalled.as_str()
+                }
+                None => {
+                    report_error("No previous expression", quiet, session.color);
+                    continue;
+                }
+            }
+        } else {
+            input
+        };
+
+        for (statement, silent) in split_statements(input) {
+            if !run_statement(statement, silent, quiet, &mut session, &mut log) {
+                break;
+            }
         }
     }
-}
 
-fn evaluate_expression(expression: &str) -> Result<f64, String> {
-    // Tokenize the expression
-    let tokens = tokenize(expression)?;
+    // A failed `assert` in a script fails the run
+    let status = if batch && session.failed_assertions > 0 {
+        1
+    } else {
+        0
+    };
 
-    // Parse the tokens into a syntax tree
-    let tree = parse_tokens(tokens)?;
+    // Nothing evaluated successfully, so there is no final result to report
+    if session.last_input.is_none() {
+        process::exit(status);
+    }
+
+    let result = match format_result(&session.ans, &session) {
+        Ok(result) => result,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            process::exit(1);
+        }
+    };
+    if quiet {
+        println!("{}", result);
+    }
+    if let Err(error) = write_out(&options, &result) {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    }
+    process::exit(status);
+}
 
-    // Evaluate the syntax tree recursively
-    let result = evaluate_tree(&tree)?;
+// Apply the --config file, or the default one in the home directory if present
+fn load_config(options: &Options, session: &mut Session) -> Result<(), String> {
+    let (path, text) = match &options.config {
+        Some(path) => {
+            let text = fs::read_to_string(path)
+                .map_err(|error| format!("Failed to read '{}': {}", path, error))?;
+            (path.clone(), text)
+        }
+        None => {
+            let Some(home) = env::var_os("HOME") else {
+                return Ok(());
+            };
+            let path = Path::new(&home).join(DEFAULT_CONFIG_FILE);
+            match fs::read_to_string(&path) {
+                Ok(text) => (path.display().to_string(), text),
+                Err(_) => return Ok(()),
+            }
+        }
+    };
 
-    Ok(result)
+    for warning in apply_config(&text, session) {
+        eprintln!("warning: {}: {}", path, warning);
+    }
+    Ok(())
 }
 
-// Tokenize the input expression
-fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
-    let mut tokens = Vec::new();
-    let mut buffer = String::new();
+// Split a line into its `;`-separated statements. A statement followed by
+// `;` is silent: it still runs, but its result is not printed, so `x = 5;`
+// assigns quietly and `x = 5; x + 1` prints only 6. Empty statements are
+// skipped.
+fn split_statements(input: &str) -> Vec<(&str, bool)> {
+    let mut statements = Vec::new();
+    let mut rest = input;
+    while let Some((statement, after)) = rest.split_once(';') {
+        statements.push((statement.trim(), true));
+        rest = after;
+    }
+    statements.push((rest.trim(), false));
+    statements.retain(|(statement, _)| !statement.is_empty());
+    statements
+}
 
-    for c in expression.chars() {
-        match c {
-            '+' | '-' | '*' | '/' => {
-                if !buffer.is_empty() {
-                    tokens.push(Token::Number(buffer.parse().unwrap()));
-                    buffer.clear();
+// Run a command or evaluate a statement, printing its output unless silent.
+// Returns false on error, so the rest of the line is abandoned.
+fn run_statement(
+    input: &str,
+    silent: bool,
+    quiet: bool,
+    session: &mut Session,
+    log: &mut Option<File>,
+) -> bool {
+    if let Some(output) = run_command(input, session) {
+        return match output {
+            Ok(message) => {
+                write_log(log, input, &message);
+                if !quiet && !silent {
+                    println!("{}", message);
                 }
-                tokens.push(Token::Operator(c));
+                true
             }
-            '(' => {
-                if !buffer.is_empty() {
-                    return Err("Invalid expression format".to_string());
-                }
-                tokens.push(Token::LeftParenthesis);
+            Err(error) => {
+                write_log(log, input, &format!("Error: {}", error));
+                report_error(&error, quiet, session.color);
+                false
             }
-            ')' => {
-                if !buffer.is_empty() {
-                    tokens.push(Token::Number(buffer.parse().unwrap()));
-                    buffer.clear();
+        };
+    }
+
+    let result = evaluate_input(input, session);
+    for note in session.take_notes() {
+        report_note(&note, quiet);
+    }
+    report_profile(session);
+    match result.and_then(|value| Ok((format_result(&value, session)?, value))) {
+        Ok((result, value)) => {
+            write_log(log, input, &result);
+            if !quiet && !silent {
+                let unit = if session.shows_angle_unit() { "°" } else { "" };
+                let result = paint(&format!("{}{}", result, unit), GREEN, session.color);
+                if session.verbose_mode {
+                    println!("Result: {} : {}", result, value.type_name());
+                } else {
+                    println!("Result: {}", result);
                 }
-                tokens.push(Token::RightParenthesis);
             }
-            '0'..='9' | '.' => buffer.push(c),
-            ' ' => continue,
-            _ => return Err("Invalid character in expression".to_string()),
+            session.record(input, value);
+            true
+        }
+        Err(error) => {
+            write_log(log, input, &format!("Error: {}", error));
+            report_error(&error, quiet, session.color);
+            false
         }
     }
+}
+
+// Open the `--log` file for appending, creating it if needed
+fn open_log(options: &Options) -> Result<Option<File>, String> {
+    let Some(path) = &options.log else {
+        return Ok(None);
+    };
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map(Some)
+        .map_err(|error| format!("Failed to open '{}': {}", path, error))
+}
 
-    if !buffer.is_empty() {
-        tokens.push(Token::Number(buffer.parse().unwrap()));
+// Append `input => outcome` to the log, flushed so the log is complete
+// even if the session is killed. A failed write stops further logging.
+fn write_log(log: &mut Option<File>, input: &str, outcome: &str) {
+    let Some(file) = log else {
+        return;
+    };
+    if let Err(error) = writeln!(file, "{} => {}", input, outcome).and_then(|_| file.flush()) {
+        eprintln!("Error: Failed to write log: {}", error);
+        *log = None;
     }
+}
 
-    Ok(tokens)
+// Evaluate one line of input: a `repeat` or a plain statement
+fn evaluate_input(input: &str, session: &mut Session) -> Result<Value, String> {
+    match input.strip_prefix("repeat ") {
+        Some(rest) => repeat_statement(rest, session),
+        None => evaluate_statement(input, session),
+    }
 }
 
-// Parse tokens into a syntax tree
-fn parse_tokens(tokens: Vec<Token>) -> Result<Vec<Token>, String> {
-    let mut output: Vec<Token> = Vec::new();
-    let mut operators: Vec<Token> = Vec::new();
+// `--stdin-once`: read exactly one line, print its result and return the exit
+// code (0 on success, 1 on any error)
+fn run_stdin_once(session: &mut Session, options: &Options) -> i32 {
+    let mut input = String::new();
+    if let Err(error) = io::stdin().read_line(&mut input) {
+        eprintln!("Error: Failed to read input: {}", error);
+        return 1;
+    }
 
-    for token in tokens {
-        match token {
-            Token::Number(_) => output.push(token),
-            Token::Operator(op) => {
-                while let Some(top_op) = operators.last() {
-                    if let Token::Operator(top_char) = *top_op {
-                        if precedence(op) <= precedence(top_char) {
-                            output.push(operators.pop().unwrap());
-                            continue;
-                        }
-                    }
-                    break;
-                }
-                operators.push(Token::Operator(op));
-            }
-            Token::LeftParenthesis => operators.push(token),
-            Token::RightParenthesis => {
-                while let Some(top) = operators.pop() {
-                    if let Token::LeftParenthesis = top {
-                        break;
-                    }
-                    output.push(top);
-                }
+    let result = evaluate_input(input.trim(), session);
+    for note in session.take_notes() {
+        eprintln!("{}", note);
+    }
+    report_profile(session);
+    match result.and_then(|value| format_result(&value, session)) {
+        Ok(result) => {
+            println!("{}", result);
+            if let Err(error) = write_out(options, &result) {
+                eprintln!("Error: {}", error);
+                return 1;
             }
+            0
+        }
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            1
         }
     }
+}
 
-    while let Some(op) = operators.pop() {
-        output.push(op);
+// `--check-only`: report every line with a syntax error on stderr and return
+// the exit code (1 if there were any)
+fn run_check_only(session: &Session) -> i32 {
+    let mut errors = 0;
+    for (index, line) in io::stdin().lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(error) => {
+                eprintln!("Error: Failed to read input: {}", error);
+                return 1;
+            }
+        };
+        if line.trim() == "." || line.trim() == "!!" {
+            continue;
+        }
+        for (statement, _) in split_statements(&line) {
+            if let Err(error) = check_input(statement, session) {
+                eprintln!("line {}: {}", index + 1, error);
+                errors += 1;
+            }
+        }
+    }
+    if errors > 0 {
+        1
+    } else {
+        0
     }
-
-    Ok(output)
 }
 
-// Evaluate the syntax tree
-fn evaluate_tree(tokens: &[Token]) -> Result<f64, String> {
-    let mut stack: Vec<f64> = Vec::new();
+// Write the final result to the `--out` file, if one was given
+fn write_out(options: &Options, result: &str) -> Result<(), String> {
+    match &options.out {
+        Some(path) => fs::write(path, format!("{}\n", result))
+            .map_err(|error| format!("Failed to write '{}': {}", path, error)),
+        None => Ok(()),
+    }
+}
 
-    for token in tokens {
-        match token {
-            Token::Number(num) => stack.push(*num),
-            Token::Operator(op) => {
-                if stack.len() < 2 {
-                    return Err("Invalid expression format".to_string());
-                }
-                let operand2 = stack.pop().unwrap();
-                let operand1 = stack.pop().unwrap();
-                let result = match op {
-                    '+' => operand1 + operand2,
-                    '-' => operand1 - operand2,
-                    '*' => operand1 * operand2,
-                    '/' => {
-                        if operand2 == 0.0 {
-                            return Err("Division by zero".to_string());
-                        }
-                        operand1 / operand2
-                    }
-                    _ => return Err("Invalid operator".to_string()),
-                };
-                stack.push(result);
-            }
-            _ => return Err("Invalid token in expression".to_string()),
-        }
+// Stage timings go to stderr so they never mix with results
+fn report_profile(session: &Session) {
+    if !session.profiling {
+        return;
     }
 
-    if stack.len() != 1 {
-        return Err("Invalid expression format".to_string());
+    let profile = session.take_profile();
+    eprintln!("profile: tokenize {:?}", profile.tokenize);
+    eprintln!("profile: parse {:?}", profile.parse);
+    eprintln!("profile: evaluate {:?}", profile.evaluate);
+}
+
+fn report_note(note: &str, quiet: bool) {
+    if quiet {
+        eprintln!("{}", note);
+    } else {
+        println!("{}", note);
     }
+}
 
-    Ok(stack[0])
+// Quiet mode keeps stdout for the final result, so errors go to stderr
+fn report_error(error: &str, quiet: bool, color: bool) {
+    if quiet {
+        eprintln!("Error: {}", error);
+    } else {
+        println!("{}", paint(&format!("Error: {}", error), RED, color));
+    }
 }
 
-// Define operator precedence
-fn precedence(op: char) -> u8 {
-    match op {
-        '+' | '-' => 1,
-        '*' | '/' => 2,
-        _ => 0,
+// Wrap text in an ANSI colour escape when colour output is on
+fn paint(text: &str, code: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
     }
 }
@@ -0,0 +1,243 @@
+use crate::lexer::Token;
+use crate::session::{Associativity, Session};
+
+// Unary minus binds tighter than * and / but looser than ^, so -2^2 is -4
+const UNARY_PRECEDENCE: u8 = 7;
+
+// Parse tokens into a syntax tree
+pub(crate) fn parse_tokens(tokens: Vec<Token>, session: &Session) -> Result<Vec<Token>, String> {
+    parse_spanned(tokens, &[], session)
+}
+
+// Parse tokens whose character offsets are known, so errors can say where
+// they are. Offsets may be missing (the public `parse` has none), in which
+// case errors just describe the tokens.
+pub(crate) fn parse_spanned(
+    tokens: Vec<Token>,
+    positions: &[usize],
+    session: &Session,
+) -> Result<Vec<Token>, String> {
+    let mut output: Vec<Token> = Vec::new();
+    let mut operators: Vec<Token> = Vec::new();
+    // Argument counts for the function calls currently open
+    let mut arg_counts: Vec<usize> = Vec::new();
+    // True where a value (not a binary operator) is expected next
+    let mut expect_operand = true;
+
+    let unexpected = |index: usize, token: &Token, expect_operand: bool| {
+        let found = describe(token);
+        let at = match positions.get(index) {
+            Some(position) => format!(" at position {}", position + 1),
+            None => String::new(),
+        };
+        if expect_operand {
+            format!("Expected a number or '(' but found {}{}", found, at)
+        } else {
+            format!("Expected an operator or ')' but found {}{}", found, at)
+        }
+    };
+
+    let mut tokens = tokens.into_iter().enumerate().peekable();
+    while let Some((index, token)) = tokens.next() {
+        match token {
+            Token::Number(_)
+            | Token::Integer(_)
+            | Token::Imaginary(_)
+            | Token::Identifier(_)
+            | Token::LeftParenthesis
+                if !expect_operand =>
+            {
+                return Err(unexpected(index, &token, expect_operand));
+            }
+            Token::Operator(op) if op == "-" && expect_operand => operators.push(Token::UnaryMinus),
+            // Prefix `+` changes nothing
+            Token::Operator(op) if op == "+" && expect_operand => {}
+            // `* 5`, `(/ 2)`, `2 + / 3`
+            Token::Operator(op) if expect_operand => {
+                return Err(format!("Expected a value before operator '{}'", op));
+            }
+            Token::RightParenthesis | Token::Comma if expect_operand => {
+                return Err(unexpected(index, &token, expect_operand));
+            }
+            Token::Number(_) | Token::Integer(_) | Token::Imaginary(_) => {
+                output.push(token);
+                expect_operand = false;
+            }
+            // `defined(x)` names x rather than evaluating it
+            Token::Identifier(name) if name == "defined" => {
+                let mut lookahead = tokens.clone();
+                match (lookahead.next(), lookahead.next(), lookahead.next()) {
+                    (
+                        Some((_, Token::LeftParenthesis)),
+                        Some((_, Token::Identifier(target))),
+                        Some((_, Token::RightParenthesis)),
+                    ) => {
+                        tokens = lookahead;
+                        output.push(Token::Defined(target));
+                        expect_operand = false;
+                    }
+                    _ => return Err("Usage: defined(name)".to_string()),
+                }
+            }
+            Token::Identifier(name) => {
+                if let Some((_, Token::LeftParenthesis)) = tokens.peek() {
+                    tokens.next();
+                    // An empty call, `f()`, takes no arguments
+                    if let Some((_, Token::RightParenthesis)) = tokens.peek() {
+                        tokens.next();
+                        output.push(Token::Function(name, 0));
+                        expect_operand = false;
+                        continue;
+                    }
+                    operators.push(Token::Function(name, 0));
+                    operators.push(Token::LeftParenthesis);
+                    arg_counts.push(1);
+                    expect_operand = true;
+                } else {
+                    output.push(Token::Identifier(name));
+                    expect_operand = false;
+                }
+            }
+            // Postfix operators bind tighter than anything, so their operand is complete
+            Token::Postfix(op) => {
+                if expect_operand {
+                    return Err(format!("Missing value before '{}'", op));
+                }
+                output.push(token);
+            }
+            Token::Operator(op) => {
+                let op_precedence = precedence(&op, session);
+                let right_associative = is_right_associative(&op, session);
+                while let Some(top) = operators.last() {
+                    match stack_precedence(top, session) {
+                        Some(top_precedence)
+                            if op_precedence < top_precedence
+                                || (op_precedence == top_precedence && !right_associative) =>
+                        {
+                            output.push(operators.pop().unwrap());
+                        }
+                        _ => break,
+                    }
+                }
+                operators.push(Token::Operator(op));
+                expect_operand = true;
+            }
+            Token::LeftParenthesis => {
+                operators.push(token);
+                expect_operand = true;
+            }
+            Token::Comma => {
+                let count = arg_counts
+                    .last_mut()
+                    .ok_or_else(|| "Unexpected ',' outside a function call".to_string())?;
+                *count += 1;
+                while let Some(top) = operators.last() {
+                    if let Token::LeftParenthesis = top {
+                        break;
+                    }
+                    output.push(operators.pop().unwrap());
+                }
+                expect_operand = true;
+            }
+            Token::RightParenthesis => {
+                loop {
+                    match operators.pop() {
+                        Some(Token::LeftParenthesis) => break,
+                        Some(top) => output.push(top),
+                        None => return Err("Unmatched ')'".to_string()),
+                    }
+                }
+                if let Some(Token::Function(..)) = operators.last() {
+                    if let Some(Token::Function(name, _)) = operators.pop() {
+                        output.push(Token::Function(name, arg_counts.pop().unwrap()));
+                    }
+                }
+                expect_operand = false;
+            }
+            Token::UnaryMinus | Token::Function(..) | Token::Defined(_) => {
+                return Err("Invalid token in expression".to_string())
+            }
+        }
+    }
+
+    if expect_operand {
+        return Err("Expected a number or '(' but found end of input".to_string());
+    }
+
+    while let Some(op) = operators.pop() {
+        match op {
+            Token::LeftParenthesis if !session.auto_close => {
+                return Err("Unmatched '('".to_string())
+            }
+            // Close the parenthesis here, completing its call if it has one
+            Token::LeftParenthesis => {
+                if let Some(Token::Function(..)) = operators.last() {
+                    if let Some(Token::Function(name, _)) = operators.pop() {
+                        output.push(Token::Function(name, arg_counts.pop().unwrap()));
+                    }
+                }
+            }
+            _ => output.push(op),
+        }
+    }
+
+    Ok(output)
+}
+
+// How a token is named in error messages
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Integer(n) => format!("'{}'", n),
+        Token::Number(x) => format!("'{}'", x),
+        Token::Imaginary(x) => format!("'{}i'", x),
+        Token::Identifier(name) | Token::Operator(name) => format!("'{}'", name),
+        Token::LeftParenthesis => "'('".to_string(),
+        Token::RightParenthesis => "')'".to_string(),
+        Token::Comma => "','".to_string(),
+        Token::Postfix(op) => format!("'{}'", op),
+        Token::UnaryMinus => "'-'".to_string(),
+        Token::Defined(name) => format!("'defined({})'", name),
+        Token::Function(name, _) => format!("'{}('", name),
+    }
+}
+
+// Precedence of an operator waiting on the parser's stack, if it is one
+fn stack_precedence(token: &Token, session: &Session) -> Option<u8> {
+    match token {
+        Token::Operator(op) => Some(precedence(op, session)),
+        Token::UnaryMinus => Some(UNARY_PRECEDENCE),
+        _ => None,
+    }
+}
+
+// Define operator precedence
+pub(crate) fn precedence(op: &str, session: &Session) -> u8 {
+    // A flat precedence makes the parser pop every time, i.e. left to right
+    if session.simple_mode {
+        return 1;
+    }
+
+    match op {
+        "or" => 1,
+        "and" => 2,
+        "==" | "!=" | "~=" | "<" | "<=" | ">" | ">=" => 3,
+        "<<" | ">>" => 4,
+        "+" | "-" => 5,
+        "*" | "/" | "//" | "mod" => 6,
+        "^" => 8,
+        _ => session
+            .custom_operator(op)
+            .map_or(0, |custom| custom.precedence),
+    }
+}
+
+// `2^3^2` is `2^(3^2)`; everything else built in groups left to right
+fn is_right_associative(op: &str, session: &Session) -> bool {
+    if session.simple_mode {
+        return false;
+    }
+    op == "^"
+        || session
+            .custom_operator(op)
+            .is_some_and(|custom| custom.associativity == Associativity::Right)
+}
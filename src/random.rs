@@ -0,0 +1,33 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Small non-cryptographic generator (xorshift64*) behind `random()`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        // The state must never be zero; mixing also spreads nearby seeds apart
+        let state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        Rng { state }
+    }
+
+    // Seeded from the clock, so each run gives a different sequence
+    pub(crate) fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Rng::new(nanos)
+    }
+
+    // Uniform in [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let bits = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        // The top 53 bits fill an f64 mantissa exactly
+        (bits >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,50 @@
+use crate::value::Value;
+
+// Exact arithmetic on fractions for `mode frac`. Each operand is a
+// numerator/denominator pair with a positive denominator. None means the
+// operation can't be done exactly (overflow, a fractional exponent) and the
+// caller falls back to floats.
+pub(crate) fn apply(op: &str, a: (i64, i64), b: (i64, i64)) -> Option<Value> {
+    let ((n1, d1), (n2, d2)) = ((a.0 as i128, a.1 as i128), (b.0 as i128, b.1 as i128));
+    match op {
+        "+" => reduced(n1 * d2 + n2 * d1, d1 * d2),
+        "-" => reduced(n1 * d2 - n2 * d1, d1 * d2),
+        "*" => reduced(n1 * n2, d1 * d2),
+        "/" if n2 != 0 => reduced(n1 * d2, d1 * n2),
+        "^" if d2 == 1 => {
+            let power = u32::try_from(n2.unsigned_abs()).ok()?;
+            let (n, d) = (n1.checked_pow(power)?, d1.checked_pow(power)?);
+            match n2 < 0 {
+                true if n != 0 => reduced(d, n),
+                true => None,
+                false => reduced(n, d),
+            }
+        }
+        _ => None,
+    }
+}
+
+// `n/d` in lowest terms with a positive denominator; a whole number becomes
+// an `Int`, so `3/4 + 1/4` is exactly 1
+pub(crate) fn reduced(numerator: i128, denominator: i128) -> Option<Value> {
+    if denominator == 0 {
+        return None;
+    }
+
+    let divisor = gcd(numerator, denominator);
+    let sign = if denominator < 0 { -1 } else { 1 };
+    let numerator = i64::try_from(sign * numerator / divisor).ok()?;
+    let denominator = i64::try_from(sign * denominator / divisor).ok()?;
+    Some(match denominator {
+        1 => Value::Int(numerator),
+        _ => Value::Ratio(numerator, denominator),
+    })
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
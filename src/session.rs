@@ -0,0 +1,1271 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::algebra::{solve, split_equation};
+use crate::cache::{cache_key, is_cacheable, ResultCache};
+use crate::calculus::differentiate;
+use crate::eval::{apply_operator, constant, evaluate_scoped, returns_angle};
+use crate::functions::UserFunction;
+use crate::lexer::{is_identifier, tokenize_spanned};
+use crate::parser::parse_spanned;
+use crate::random::Rng;
+use crate::value::Value;
+use crate::CalcError;
+use crate::{evaluate_expression, parse_within_limits};
+
+// Upper bound for `repeat N <expr>` so a typo can't lock up the REPL
+const MAX_REPEAT_COUNT: usize = 10_000;
+
+// Default cap on expression length, in characters
+const DEFAULT_MAX_INPUT_LENGTH: usize = 4096;
+
+// Default cost budget for one parsed expression
+const DEFAULT_MAX_COST: usize = 100_000;
+
+// Default number of user function calls allowed in one evaluation
+const DEFAULT_MAX_ITERATIONS: usize = 1000;
+
+// Default for `tolerance`
+const DEFAULT_TOLERANCE: f64 = 1e-9;
+
+// Nesting of user function calls is bounded regardless of the budget, as
+// each level takes native stack
+const MAX_CALL_DEPTH: usize = 1000;
+
+// f64 holds no more significant digits than this
+pub(crate) const MAX_SIGNIFICANT_DIGITS: usize = 17;
+
+// Oldest entries are dropped once history grows past this
+const MAX_HISTORY_LENGTH: usize = 1000;
+
+// Characters the built-in syntax already gives a meaning, which
+// `register_operator` refuses
+const RESERVED_SYMBOLS: &str = "+-*/^<>=!~()[]|,.%°:;_⁰¹²³⁴⁵⁶⁷⁸⁹⁻";
+
+/// How results are written out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayMode {
+    Auto,
+    // Always the same number of decimal places, zeros kept
+    Fixed,
+    // One digit before the point and a power-of-ten exponent
+    Scientific,
+    // Exponents restricted to multiples of 3, matching SI prefixes
+    Engineering,
+    // Two decimal places with thousands separators, e.g. $1,234.50
+    Currency,
+}
+
+/// How trigonometric functions interpret and report angles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AngleMode {
+    Radians,
+    Degrees,
+}
+
+/// Which way a chain of the same operator groups: `a $ b $ c` is
+/// `(a $ b) $ c` for `Left` and `a $ (b $ c)` for `Right`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+// A binary operator added with `register_operator`
+#[derive(Clone, Copy)]
+pub(crate) struct CustomOperator {
+    pub(crate) precedence: u8,
+    pub(crate) associativity: Associativity,
+    pub(crate) function: fn(f64, f64) -> Result<f64, CalcError>,
+}
+
+/// Time spent in each stage of the pipeline, summed over the expressions
+/// evaluated since the last [`Session::take_profile`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Profile {
+    pub tokenize: Duration,
+    pub parse: Duration,
+    pub evaluate: Duration,
+}
+
+/// State shared by every expression entered during one run
+pub struct Session {
+    pub variables: HashMap<String, Value>,
+    // Variables defined with `const`, which cannot be reassigned
+    pub constants: HashSet<String>,
+    // Functions defined with `name(params) = body`
+    pub(crate) functions: HashMap<String, UserFunction>,
+    // Binary operators added with `register_operator`
+    pub(crate) operators: HashMap<char, CustomOperator>,
+    // Result of the last successful evaluation, exposed as `ans` and `_`;
+    // 0 before the first one
+    pub ans: Value,
+    // Significant digits each float result keeps when stored as `ans`
+    pub round_ans: Option<usize>,
+    // Smallest and largest top-level results so far, as `runmin` and `runmax`
+    pub(crate) running: Option<(Value, Value)>,
+    // Raw text of the last successful evaluation, echoed by `last`
+    pub last_input: Option<String>,
+    // Successful evaluations, oldest first, browsed with `prev` and `next`
+    pub history: Vec<String>,
+    // What each entry in `history` evaluated to, in the same order
+    pub(crate) results: Vec<Value>,
+    // Index into `history` picked by `prev`/`next`; None is past the newest
+    pub(crate) recall: Option<usize>,
+    /// Longer expressions are rejected before tokenizing. The length is
+    /// counted in characters, not bytes.
+    ///
+    /// ```
+    /// use calc::{evaluate_expression, evaluate_statement, Session, Value};
+    ///
+    /// let mut session = Session::new();
+    /// session.max_input_length = 5;
+    /// assert_eq!(evaluate_expression("1+2+3", &session), Ok(Value::Int(6)));
+    /// assert_eq!(evaluate_expression("2³+10", &session), Ok(Value::Int(18)));
+    /// assert_eq!(
+    ///     evaluate_expression("1+2+3 ", &session),
+    ///     Err("Expression too long".to_string())
+    /// );
+    ///
+    /// // Cached results are held to the limit too
+    /// session.enable_cache(16);
+    /// evaluate_statement("2^10", &mut session).unwrap();
+    /// session.max_input_length = 3;
+    /// assert_eq!(
+    ///     evaluate_statement("2^10", &mut session),
+    ///     Err("Expression too long".to_string())
+    /// );
+    /// ```
+    pub max_input_length: usize,
+    /// Budget for one parsed expression, checked before evaluating it. Each
+    /// value and operator costs 1 and each function call 10 more, so short
+    /// but deeply nested calls are caught as well as long expressions.
+    ///
+    /// ```
+    /// use calc::{evaluate_expression, Session};
+    ///
+    /// let mut session = Session::new();
+    /// session.max_cost = 150;
+    /// let nested = format!("{}2{}", "sqrt(".repeat(10), ")".repeat(10));
+    /// assert!(evaluate_expression(&nested, &session).is_ok());
+    ///
+    /// let nested = format!("{}2{}", "sqrt(".repeat(20), ")".repeat(20));
+    /// assert_eq!(
+    ///     evaluate_expression(&nested, &session),
+    ///     Err("Expression too complex".to_string())
+    /// );
+    /// ```
+    ///
+    /// The budget applies to cached results too, so lowering it rejects an
+    /// expression that was evaluated before:
+    ///
+    /// ```
+    /// use calc::{evaluate_statement, run_command, Session, Value};
+    ///
+    /// let mut session = Session::new();
+    /// session.enable_cache(16);
+    /// assert_eq!(evaluate_statement("1+2+3", &mut session), Ok(Value::Int(6)));
+    /// run_command("max-cost 1", &mut session);
+    /// assert_eq!(
+    ///     evaluate_statement("1+2+3", &mut session),
+    ///     Err("Expression too complex".to_string())
+    /// );
+    /// ```
+    ///
+    /// Commands and calls that evaluate an expression of their own, such as
+    /// `benchmark`, `map` and `solve`, are held to the same budget:
+    ///
+    /// ```
+    /// use calc::{evaluate_statement, run_command, Session};
+    ///
+    /// let mut session = Session::new();
+    /// evaluate_statement("data = 1, 2, 3", &mut session).unwrap();
+    /// run_command("max-cost 3", &mut session);
+    /// let too_complex = "Expression too complex".to_string();
+    /// assert_eq!(
+    ///     run_command("benchmark 10 1+2+3", &mut session),
+    ///     Some(Err(too_complex.clone()))
+    /// );
+    /// assert!(evaluate_statement("map(data, x^2)", &mut session).is_ok());
+    /// assert_eq!(evaluate_statement("map(data, x^2+1)", &mut session), Err(too_complex.clone()));
+    /// assert_eq!(evaluate_statement("solve(x+1+2 = 5, x)", &mut session), Err(too_complex));
+    /// ```
+    pub max_cost: usize,
+    /// User function calls allowed per evaluation before giving up
+    ///
+    /// ```
+    /// use calc::{evaluate_expression, run_command, Session, Value};
+    ///
+    /// let mut session = Session::new();
+    /// run_command("forever(x) = forever(x + 1)", &mut session);
+    /// assert_eq!(
+    ///     evaluate_expression("forever(1)", &session),
+    ///     Err("Evaluation limit exceeded".to_string())
+    /// );
+    ///
+    /// // The budget is per evaluation, not per session
+    /// run_command("twice(x) = 2 * x", &mut session);
+    /// session.max_iterations = 3;
+    /// assert_eq!(evaluate_expression("twice(twice(twice(1)))", &session), Ok(Value::Int(8)));
+    /// assert_eq!(evaluate_expression("twice(twice(twice(1)))", &session), Ok(Value::Int(8)));
+    /// assert!(evaluate_expression("twice(twice(twice(twice(1))))", &session).is_err());
+    /// ```
+    pub max_iterations: usize,
+    // User function calls made so far in the current evaluation, and how
+    // many of them are still running
+    calls: Cell<usize>,
+    depth: Cell<usize>,
+    /// Wall-clock limit for a single evaluation, for untrusted input.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use calc::{evaluate_expression, run_command, Session};
+    ///
+    /// let mut session = Session::new();
+    /// session.max_iterations = usize::MAX;
+    /// run_command("f0(x) = x + 1", &mut session);
+    /// for n in 1..=30 {
+    ///     let definition = format!("f{}(x) = f{}(x) + f{}(x)", n, n - 1, n - 1);
+    ///     run_command(&definition, &mut session);
+    /// }
+    ///
+    /// session.timeout = Some(Duration::from_millis(1));
+    /// assert_eq!(
+    ///     evaluate_expression("f30(1)", &session),
+    ///     Err("Evaluation timed out".to_string())
+    /// );
+    /// ```
+    pub timeout: Option<Duration>,
+    // When the current evaluation runs out of time
+    deadline: Cell<Option<Instant>>,
+    pub angle_mode: AngleMode,
+    // Mark angles from inverse trig functions with `°` in degree mode
+    pub angle_unit: bool,
+    // Whether the last expression's outermost operation returned an angle
+    pub(crate) angle_result: Cell<bool>,
+    // Apply operators strictly left to right, ignoring BODMAS precedence
+    pub simple_mode: bool,
+    /// How results are written. Engineering display also reads SI suffixes
+    /// such as `4k`, so results cached in one mode are not reused in another.
+    ///
+    /// ```
+    /// use calc::{evaluate_statement, run_command, Session, Value};
+    ///
+    /// let mut session = Session::new();
+    /// session.enable_cache(16);
+    /// run_command("display eng", &mut session);
+    /// assert_eq!(evaluate_statement("4k", &mut session), Ok(Value::Float(4000.0)));
+    /// run_command("display auto", &mut session);
+    /// assert!(evaluate_statement("4k", &mut session).is_err());
+    /// ```
+    pub display_mode: DisplayMode,
+    // Symbol placed before amounts in currency display
+    pub currency_symbol: String,
+    // Characters printed between thousands (where grouped) and before the
+    // fraction, e.g. `.` and `,` for 1.234,56
+    pub group_separator: char,
+    pub decimal_separator: char,
+    // Decimal places shown; None prints the full value
+    pub precision: Option<usize>,
+    // Set by `precision auto`: with no precision, write very large and very
+    // small floats as `<mantissa>e<exponent>` rather than in full
+    pub shortest: bool,
+    // Significant figures shown in auto display; takes the place of `precision`
+    pub sigfigs: Option<usize>,
+    // Radix for whole-number results: 2, 8, 10 or 16
+    pub output_base: u32,
+    // Make a fractional result in base 2, 8 or 16 an error instead of showing it in decimal
+    pub strict_base: bool,
+    // Show negative numbers in other bases as 64-bit two's complement
+    pub twos_complement: bool,
+    // Colour results and errors with ANSI escapes in the REPL
+    pub color: bool,
+    /// Decimal places for exact `+ - * /` in `mode decimal`; None uses floats
+    ///
+    /// ```
+    /// use calc::{evaluate_expression, run_command, Session, Value};
+    ///
+    /// let mut session = Session::new();
+    /// assert_eq!(evaluate_expression("0.1 + 0.2 == 0.3", &session), Ok(Value::Bool(false)));
+    /// run_command("mode decimal", &mut session);
+    /// assert_eq!(evaluate_expression("0.1 + 0.2 == 0.3", &session), Ok(Value::Bool(true)));
+    /// assert_eq!(evaluate_expression("1.1 * 1.1", &session), Ok(Value::Float(1.21)));
+    /// ```
+    pub decimal_scale: Option<u32>,
+    // Largest difference, relative to the operands' size once above 1, at
+    // which `~=` and `assert` still treat two values as equal
+    pub tolerance: f64,
+    // `assert` checks that have failed, so scripts can exit with an error
+    pub failed_assertions: usize,
+    // Keep uneven division of whole numbers as an exact fraction
+    pub frac_mode: bool,
+    // Reject infinite and NaN results rather than passing them through
+    pub strict_mode: bool,
+    // Point out `/` between whole numbers that yields a fraction
+    pub warn_mode: bool,
+    // Print boolean results as true/false rather than 1/0
+    pub bool_mode: bool,
+    // Follow each printed result with its type, e.g. `14 : int`
+    pub verbose_mode: bool,
+    // Name a missing `*` between adjacent values such as `2(3)`
+    pub strict_paren: bool,
+    // Read `i` as the imaginary unit and allow complex results
+    pub complex_mode: bool,
+    // Read `a + b%` and `a - b%` as adding or removing b percent of a
+    pub finance_mode: bool,
+    // Read `1:30` as 90 and `1:30:30` as 90.5: hours and minutes, with
+    // optional seconds, counted in minutes
+    pub time_mode: bool,
+    // Close parentheses still open at the end of an expression instead of erroring
+    pub auto_close: bool,
+    // Record how long each pipeline stage takes
+    pub profiling: bool,
+    profile: Cell<Profile>,
+    // Source of `random()`
+    rng: Cell<Rng>,
+    // Notes raised while evaluating, collected for the caller to print
+    notes: RefCell<Vec<String>>,
+    // Results of deterministic expressions, when enabled
+    pub(crate) cache: Option<ResultCache>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session {
+            variables: HashMap::new(),
+            constants: HashSet::new(),
+            functions: HashMap::new(),
+            operators: HashMap::new(),
+            ans: Value::Int(0),
+            round_ans: None,
+            running: None,
+            last_input: None,
+            history: Vec::new(),
+            results: Vec::new(),
+            recall: None,
+            max_input_length: DEFAULT_MAX_INPUT_LENGTH,
+            max_cost: DEFAULT_MAX_COST,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            calls: Cell::new(0),
+            depth: Cell::new(0),
+            timeout: None,
+            deadline: Cell::new(None),
+            angle_mode: AngleMode::Radians,
+            angle_unit: false,
+            angle_result: Cell::new(false),
+            simple_mode: false,
+            display_mode: DisplayMode::Auto,
+            currency_symbol: "$".to_string(),
+            group_separator: ',',
+            decimal_separator: '.',
+            precision: None,
+            shortest: false,
+            sigfigs: None,
+            output_base: 10,
+            strict_base: false,
+            twos_complement: false,
+            color: false,
+            decimal_scale: None,
+            tolerance: DEFAULT_TOLERANCE,
+            failed_assertions: 0,
+            frac_mode: false,
+            strict_mode: false,
+            warn_mode: false,
+            bool_mode: false,
+            verbose_mode: false,
+            strict_paren: false,
+            complex_mode: false,
+            finance_mode: false,
+            time_mode: false,
+            auto_close: false,
+            profiling: false,
+            profile: Cell::new(Profile::default()),
+            rng: Cell::new(Rng::from_time()),
+            notes: RefCell::new(Vec::new()),
+            cache: None,
+        }
+    }
+
+    /// Keep the results of up to `capacity` expressions that give the same
+    /// value every time, such as `2^10` but not `x + 1` or `random()`. A
+    /// cached result is used only under the settings it was evaluated with.
+    ///
+    /// ```
+    /// use calc::{evaluate_statement, run_command, Profile, Session, Value};
+    ///
+    /// let mut session = Session::new();
+    /// session.enable_cache(16);
+    /// session.profiling = true;
+    /// assert_eq!(evaluate_statement("2^10", &mut session), Ok(Value::Int(1024)));
+    /// assert_ne!(session.take_profile().evaluate, Default::default());
+    /// // A hit skips evaluation altogether
+    /// assert_eq!(evaluate_statement("2^10", &mut session), Ok(Value::Int(1024)));
+    /// assert_eq!(session.take_profile(), Profile::default());
+    ///
+    /// assert_eq!(evaluate_statement("1 ~= 1.1", &mut session), Ok(Value::Bool(false)));
+    /// run_command("tolerance 0.5", &mut session);
+    /// assert_eq!(evaluate_statement("1 ~= 1.1", &mut session), Ok(Value::Bool(true)));
+    ///
+    /// // Expressions reading a variable are evaluated afresh every time
+    /// evaluate_statement("x = 1", &mut session).unwrap();
+    /// assert_eq!(evaluate_statement("x + 1", &mut session), Ok(Value::Int(2)));
+    /// evaluate_statement("x = 5", &mut session).unwrap();
+    /// assert_eq!(evaluate_statement("x + 1", &mut session), Ok(Value::Int(6)));
+    /// ```
+    pub fn enable_cache(&mut self, capacity: usize) {
+        self.cache = Some(ResultCache::new(capacity));
+    }
+
+    pub fn disable_cache(&mut self) {
+        self.cache = None;
+    }
+
+    /// Add a binary operator written as `symbol` between its operands.
+    /// `precedence` is on the scale the built-ins use: 1 for `or`, 2 for
+    /// `and`, 3 for comparisons, 4 for `<< >>`, 5 for `+ -`, 6 for `* /`
+    /// and 8 for `^`.
+    /// Operands and results are floats. Registering a symbol again replaces
+    /// it; symbols the built-in syntax uses are refused.
+    ///
+    /// ```
+    /// use calc::{evaluate_expression, Associativity, Session, Value};
+    ///
+    /// let mut session = Session::new();
+    /// session
+    ///     .register_operator('$', 6, Associativity::Left, |a, b| Ok((a + b) / 2.0))
+    ///     .unwrap();
+    /// assert_eq!(evaluate_expression("2 $ 6", &session), Ok(Value::Float(4.0)));
+    /// // Binds like `*`, so tighter than `+`
+    /// assert_eq!(evaluate_expression("1 + 2 $ 6", &session), Ok(Value::Float(5.0)));
+    /// assert_eq!(evaluate_expression("(2 $ 6) $ 0", &session), Ok(Value::Float(2.0)));
+    ///
+    /// assert!(session
+    ///     .register_operator('+', 1, Associativity::Left, |a, b| Ok(a - b))
+    ///     .is_err());
+    /// // Superscripts are exponents, as in `10⁻³`
+    /// assert!(session
+    ///     .register_operator('⁻', 1, Associativity::Left, |a, b| Ok(a - b))
+    ///     .is_err());
+    /// ```
+    pub fn register_operator(
+        &mut self,
+        symbol: char,
+        precedence: u8,
+        associativity: Associativity,
+        function: fn(f64, f64) -> Result<f64, CalcError>,
+    ) -> Result<(), CalcError> {
+        if symbol.is_alphanumeric() || symbol.is_whitespace() || RESERVED_SYMBOLS.contains(symbol) {
+            return Err(format!("'{}' cannot be used as an operator", symbol));
+        }
+        self.operators.insert(
+            symbol,
+            CustomOperator {
+                precedence,
+                associativity,
+                function,
+            },
+        );
+        // Cached results may have used an earlier operator with this symbol
+        if let Some(cache) = self.cache.as_mut() {
+            cache.clear();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn custom_operator(&self, op: &str) -> Option<&CustomOperator> {
+        let mut chars = op.chars();
+        match (chars.next(), chars.next()) {
+            (Some(symbol), None) => self.operators.get(&symbol),
+            _ => None,
+        }
+    }
+
+    // Built-in constants and names locked with `const`
+    pub(crate) fn is_constant(&self, name: &str) -> bool {
+        constant(name).is_some() || self.constants.contains(name)
+    }
+
+    pub(crate) fn reset_calls(&self) {
+        self.calls.set(0);
+        self.depth.set(0);
+        let now = Instant::now();
+        self.deadline
+            .set(self.timeout.and_then(|timeout| now.checked_add(timeout)));
+    }
+
+    pub(crate) fn check_deadline(&self) -> Result<(), String> {
+        match self.deadline.get() {
+            Some(deadline) if Instant::now() >= deadline => Err("Evaluation timed out".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    // Run a user function call, counting it against the budget
+    pub(crate) fn guard_call<T>(
+        &self,
+        call: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, String> {
+        let calls = self.calls.get() + 1;
+        if calls > self.max_iterations || self.depth.get() >= MAX_CALL_DEPTH {
+            return Err("Evaluation limit exceeded".to_string());
+        }
+        self.calls.set(calls);
+
+        self.depth.set(self.depth.get() + 1);
+        let result = call();
+        self.depth.set(self.depth.get() - 1);
+        result
+    }
+
+    /// Whether the last result should be shown with a `°`: `mode angleunit`
+    /// is on, angles are in degrees, and the last expression evaluated was an
+    /// inverse trig call such as `asin(1)` (not, say, `2 * asin(1)`).
+    ///
+    /// ```
+    /// use calc::{evaluate_statement, AngleMode, Session};
+    ///
+    /// let mut session = Session::new();
+    /// session.angle_mode = AngleMode::Degrees;
+    /// evaluate_statement("asin(1)", &mut session).unwrap();
+    /// assert!(!session.shows_angle_unit());
+    ///
+    /// session.angle_unit = true;
+    /// evaluate_statement("asin(1)", &mut session).unwrap();
+    /// assert!(session.shows_angle_unit());
+    /// evaluate_statement("sin(90)", &mut session).unwrap();
+    /// assert!(!session.shows_angle_unit());
+    /// ```
+    pub fn shows_angle_unit(&self) -> bool {
+        self.angle_unit && self.angle_mode == AngleMode::Degrees && self.angle_result.get()
+    }
+
+    /// Restart `random()` from a fixed seed, so the same seed always gives
+    /// the same sequence.
+    ///
+    /// ```
+    /// use calc::{evaluate_expression, Session};
+    ///
+    /// let mut session = Session::new();
+    /// session.seed(42);
+    /// let first = evaluate_expression("random()", &session);
+    /// let second = evaluate_expression("random()", &session);
+    /// assert_ne!(first, second);
+    ///
+    /// session.seed(42);
+    /// assert_eq!(evaluate_expression("random()", &session), first);
+    /// assert_eq!(evaluate_expression("random()", &session), second);
+    /// ```
+    pub fn seed(&mut self, seed: u64) {
+        self.rng.set(Rng::new(seed));
+    }
+
+    pub(crate) fn next_random(&self) -> f64 {
+        let mut rng = self.rng.get();
+        let value = rng.next_f64();
+        self.rng.set(rng);
+        value
+    }
+
+    pub(crate) fn add_timings(&self, tokenize: Duration, parse: Duration, evaluate: Duration) {
+        let mut profile = self.profile.get();
+        profile.tokenize += tokenize;
+        profile.parse += parse;
+        profile.evaluate += evaluate;
+        self.profile.set(profile);
+    }
+
+    /// Drain the stage timings gathered while `profiling` is on
+    pub fn take_profile(&self) -> Profile {
+        self.profile.take()
+    }
+
+    pub(crate) fn add_note(&self, note: String) {
+        self.notes.borrow_mut().push(note);
+    }
+
+    /// Drain the notes raised since the last call, e.g. by `mode warn`
+    ///
+    /// ```
+    /// use calc::{evaluate_expression, Session, Value};
+    ///
+    /// let mut session = Session::new();
+    /// session.warn_mode = true;
+    /// assert_eq!(evaluate_expression("7 / 2", &session), Ok(Value::Float(3.5)));
+    /// assert_eq!(
+    ///     session.take_notes(),
+    ///     ["note: 7 / 2 = 3.5 (use // for integer division)"]
+    /// );
+    /// assert!(session.take_notes().is_empty());
+    ///
+    /// // Division that comes out even, or of fractions, raises nothing
+    /// evaluate_expression("8 / 2 + 0.5 / 2", &session).unwrap();
+    /// assert!(session.take_notes().is_empty());
+    /// ```
+    pub fn take_notes(&self) -> Vec<String> {
+        self.notes.take()
+    }
+
+    /// Remember a successful top-level evaluation for `ans`, `last`, history
+    /// and the running `runmin`/`runmax`.
+    ///
+    /// ```
+    /// use calc::{evaluate_expression, Session, Value};
+    ///
+    /// let mut session = Session::new();
+    /// for input in ["5", "_ * 2", "_ - 7"] {
+    ///     let value = evaluate_expression(input, &session).unwrap();
+    ///     session.record(input, value);
+    /// }
+    /// assert_eq!(session.ans, Value::Int(3));
+    ///
+    /// // Stored in full, 1/3 * 3 comes back to exactly 1; rounded to four
+    /// // digits first, it doesn't
+    /// let mut session = Session::new();
+    /// for round_ans in [None, Some(4)] {
+    ///     session.round_ans = round_ans;
+    ///     let third = evaluate_expression("1 / 3", &session).unwrap();
+    ///     session.record("1 / 3", third);
+    ///     let value = evaluate_expression("ans * 3", &session).unwrap();
+    ///     session.record("ans * 3", value);
+    /// }
+    /// assert_eq!(session.history.len(), 4);
+    /// assert_eq!(evaluate_expression("ans", &session), Ok(Value::Float(0.9999)));
+    ///
+    /// let mut session = Session::new();
+    /// for input in ["5", "3", "9", "4"] {
+    ///     let value = evaluate_expression(input, &session).unwrap();
+    ///     session.record(input, value);
+    /// }
+    /// assert_eq!(evaluate_expression("runmin", &session), Ok(Value::Int(3)));
+    /// assert_eq!(evaluate_expression("runmax", &session), Ok(Value::Int(9)));
+    /// ```
+    pub fn record(&mut self, input: &str, value: Value) {
+        // `mode roundans N`: keep only what a display-precision calculator would
+        let value = match (value, self.round_ans) {
+            (Value::Float(x), Some(digits)) if x.is_finite() => {
+                Value::Float(format!("{:.*e}", digits - 1, x).parse().unwrap())
+            }
+            (value, _) => value,
+        };
+        // Complex results have no ordering, so they don't count
+        if !value.as_f64().is_nan() {
+            self.running = Some(match self.running.take() {
+                None => (value.clone(), value.clone()),
+                Some((min, max)) => (
+                    if value.as_f64() < min.as_f64() {
+                        value.clone()
+                    } else {
+                        min
+                    },
+                    if value.as_f64() > max.as_f64() {
+                        value.clone()
+                    } else {
+                        max
+                    },
+                ),
+            });
+        }
+        self.ans = value;
+        self.last_input = Some(input.to_string());
+        if self.history.len() == MAX_HISTORY_LENGTH {
+            self.history.remove(0);
+            self.results.remove(0);
+        }
+        self.history.push(input.to_string());
+        self.results.push(self.ans.clone());
+        self.recall = None;
+    }
+
+    /// Forget variables, constants and functions defined so far, and `ans`
+    pub fn reset_variables(&mut self) {
+        self.variables.clear();
+        self.constants.clear();
+        self.functions.clear();
+        self.ans = Value::Int(0);
+        if let Some(cache) = self.cache.as_mut() {
+            cache.clear();
+        }
+    }
+
+    /// Forget past inputs, for `last`, `prev` and `next`
+    pub fn reset_history(&mut self) {
+        self.last_input = None;
+        self.history.clear();
+        self.results.clear();
+        self.recall = None;
+    }
+
+    /// Put every mode and display setting back to its default, keeping
+    /// variables and history. The limits, currency symbol, colour, profiling
+    /// and cache that the program was started with stay as they are.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use calc::{run_command, DisplayMode, Session};
+    ///
+    /// let mut session = Session::new();
+    /// session.max_cost = 500;
+    /// session.max_iterations = 50;
+    /// session.max_input_length = 80;
+    /// session.timeout = Some(Duration::from_secs(1));
+    /// session.currency_symbol = "€".to_string();
+    /// session.profiling = true;
+    /// run_command("display currency", &mut session);
+    /// run_command("mode frac", &mut session);
+    ///
+    /// session.reset_modes();
+    /// assert_eq!(session.display_mode, DisplayMode::Auto);
+    /// assert!(!session.frac_mode);
+    /// assert_eq!(session.max_cost, 500);
+    /// assert_eq!(session.max_iterations, 50);
+    /// assert_eq!(session.max_input_length, 80);
+    /// assert_eq!(session.timeout, Some(Duration::from_secs(1)));
+    /// assert_eq!(session.currency_symbol, "€");
+    /// assert!(session.profiling);
+    /// ```
+    pub fn reset_modes(&mut self) {
+        let old = std::mem::take(self);
+        self.variables = old.variables;
+        self.constants = old.constants;
+        self.functions = old.functions;
+        self.operators = old.operators;
+        self.ans = old.ans;
+        self.running = old.running;
+        self.last_input = old.last_input;
+        self.history = old.history;
+        self.results = old.results;
+        self.recall = old.recall;
+        self.failed_assertions = old.failed_assertions;
+        // A seeded sequence carries on where it was
+        self.rng = old.rng;
+        self.notes = old.notes;
+        // Set up by the caller or on the command line rather than by `mode`
+        self.max_input_length = old.max_input_length;
+        self.max_cost = old.max_cost;
+        self.max_iterations = old.max_iterations;
+        self.timeout = old.timeout;
+        self.currency_symbol = old.currency_symbol;
+        self.color = old.color;
+        self.profiling = old.profiling;
+        self.profile = old.profile;
+        self.cache = old.cache;
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+/// Run `repeat N <expr>`: evaluate the statement N times and keep the last result
+///
+/// ```
+/// use calc::{evaluate_statement, repeat_statement, Session, Value};
+///
+/// let mut session = Session::new();
+/// evaluate_statement("x = 1", &mut session).unwrap();
+/// assert_eq!(repeat_statement("10 x *= 2", &mut session), Ok(Value::Int(1024)));
+/// assert_eq!(session.variables["x"], Value::Int(1024));
+///
+/// assert_eq!(
+///     repeat_statement("0 x += 1", &mut session),
+///     Err("Repeat count must be at least 1".to_string())
+/// );
+/// assert_eq!(
+///     repeat_statement("many x += 1", &mut session),
+///     Err("Invalid repeat count 'many'".to_string())
+/// );
+/// ```
+pub fn repeat_statement(input: &str, session: &mut Session) -> Result<Value, String> {
+    let input = input.trim_start();
+    let (count, statement) = input
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| "Usage: repeat N <expression>".to_string())?;
+
+    let count: usize = count
+        .parse()
+        .map_err(|_| format!("Invalid repeat count '{}'", count))?;
+    if count == 0 {
+        return Err("Repeat count must be at least 1".to_string());
+    }
+    if count > MAX_REPEAT_COUNT {
+        return Err(format!(
+            "Repeat count exceeds the limit of {}",
+            MAX_REPEAT_COUNT
+        ));
+    }
+
+    let mut result = Value::Int(0);
+    for _ in 0..count {
+        result = evaluate_statement(statement, session)?;
+    }
+
+    Ok(result)
+}
+
+/// Evaluate an expression or an assignment (`x = ...`, `x += ...`,
+/// `a, b = ...`, `const c = ...`) against the session. A trailing
+/// `at x=1, y=2` evaluates it with temporary values for those variables.
+///
+/// ```
+/// use calc::{evaluate_statement, Session, Value};
+///
+/// let mut session = Session::new();
+/// evaluate_statement("a, b = 3, 4", &mut session).unwrap();
+/// assert_eq!(session.variables["a"], Value::Int(3));
+/// assert_eq!(session.variables["b"], Value::Int(4));
+///
+/// evaluate_statement("a, b = b, a", &mut session).unwrap();
+/// assert_eq!(session.variables["a"], Value::Int(4));
+/// assert_eq!(session.variables["b"], Value::Int(3));
+///
+/// assert!(evaluate_statement("a, b = 1", &mut session).is_err());
+/// ```
+///
+/// The values given with `at` last only for that statement:
+///
+/// ```
+/// use calc::{evaluate_statement, Session, Value};
+///
+/// let mut session = Session::new();
+/// evaluate_statement("x = 100", &mut session).unwrap();
+/// assert_eq!(evaluate_statement("x^2 + y at x=3, y=1", &mut session), Ok(Value::Int(10)));
+/// assert_eq!(session.variables["x"], Value::Int(100));
+/// assert!(!session.variables.contains_key("y"));
+/// assert!(evaluate_statement("y", &mut session).is_err());
+///
+/// // Only an `at` outside brackets starts the bindings
+/// evaluate_statement("at = 2", &mut session).unwrap();
+/// assert_eq!(evaluate_statement("[x + at] * 2 at x=1", &mut session), Ok(Value::Int(6)));
+/// ```
+///
+/// A name defined with `const` cannot be assigned again:
+///
+/// ```
+/// use calc::{evaluate_statement, Session, Value};
+///
+/// let mut session = Session::new();
+/// assert_eq!(evaluate_statement("const g = 9.81", &mut session), Ok(Value::Float(9.81)));
+/// let error = Err("Cannot assign to constant 'g'".to_string());
+/// assert_eq!(evaluate_statement("g = 10", &mut session), error);
+/// assert_eq!(evaluate_statement("g += 1", &mut session), error);
+/// assert_eq!(evaluate_statement("const g = 10", &mut session), error);
+/// assert!(evaluate_statement("pi = 3", &mut session).is_err());
+/// assert_eq!(session.variables["g"], Value::Float(9.81));
+/// ```
+///
+/// A single name given several values holds them as a list, for the
+/// aggregates `sum`, `mean`, `max` and `min` and for `map(list, expr in x)`:
+///
+/// ```
+/// use calc::{evaluate_statement, format_result, Session, Value};
+///
+/// let mut session = Session::new();
+/// let data = evaluate_statement("data = 1, 2, 3, 4", &mut session).unwrap();
+/// assert_eq!(data, Value::List(vec![1.into(), 2.into(), 3.into(), 4.into()]));
+///
+/// assert_eq!(evaluate_statement("sum(data)", &mut session), Ok(Value::Int(10)));
+/// assert_eq!(evaluate_statement("mean(data)", &mut session), Ok(Value::Float(2.5)));
+/// assert_eq!(evaluate_statement("max(data)", &mut session), Ok(Value::Int(4)));
+///
+/// let squares = evaluate_statement("map(data, x^2)", &mut session).unwrap();
+/// assert_eq!(format_result(&squares, &session), Ok("1, 4, 9, 16".to_string()));
+/// assert!(evaluate_statement("data + 1", &mut session).is_err());
+/// ```
+///
+/// `diff(expr, x, at)` is the numeric derivative of `expr` at `x = at`, good
+/// to about 10 significant digits:
+///
+/// ```
+/// use calc::{evaluate_statement, Session};
+///
+/// let mut session = Session::new();
+/// let slope = evaluate_statement("diff(x^2, x, 3)", &mut session).unwrap().as_f64();
+/// assert!((slope - 6.0).abs() < 1e-9);
+/// let slope = evaluate_statement("diff(sin(x), x, 0)", &mut session).unwrap().as_f64();
+/// assert!((slope - 1.0).abs() < 1e-9);
+/// // The variable is only bound while differentiating
+/// assert!(!session.variables.contains_key("x"));
+/// assert!(evaluate_statement("diff(x^2, pi, 3)", &mut session).is_err());
+/// ```
+///
+/// `solve(lhs = rhs, x)` finds the `x` that makes a linear equation hold. A
+/// `~=` is a comparison, not the equation's `=`:
+///
+/// ```
+/// use calc::{evaluate_statement, Session, Value};
+///
+/// let mut session = Session::new();
+/// assert_eq!(evaluate_statement("solve(2*x + 1 = 7, x)", &mut session), Ok(Value::Float(3.0)));
+/// assert_eq!(
+///     evaluate_statement("solve(x ~= 3, x)", &mut session),
+///     Err("Usage: solve(lhs = rhs, variable)".to_string())
+/// );
+/// assert_eq!(
+///     evaluate_statement("solve(x + 1 = x + 2, x)", &mut session),
+///     Err("Equation has no solution".to_string())
+/// );
+/// assert_eq!(
+///     evaluate_statement("solve(2*x = x + x, x)", &mut session),
+///     Err("Equation has infinitely many solutions".to_string())
+/// );
+/// assert_eq!(
+///     evaluate_statement("solve(x^2 = 4, x)", &mut session),
+///     Err("Equation is not linear in 'x'".to_string())
+/// );
+/// ```
+pub fn evaluate_statement(input: &str, session: &mut Session) -> Result<Value, String> {
+    if let Some((statement, bindings)) = split_at_bindings(input) {
+        return evaluate_at(statement, bindings, session);
+    }
+    match split_call(input) {
+        Some(("diff", args)) => return differentiate(&args, session),
+        Some(("solve", args)) => return solve(&args, session),
+        Some(("map", args)) => return map_list(&args, session),
+        _ => {}
+    }
+    if let Some(definition) = input.trim_start().strip_prefix("const ") {
+        return define_constant(definition, session);
+    }
+
+    if let Some((names, expressions)) = split_multiple_assignment(input) {
+        return assign_all(&names, &expressions, session);
+    }
+    let Some((name, op, expression)) = split_assignment(input) else {
+        return evaluate_cached(input, session);
+    };
+
+    if session.is_constant(name) {
+        return Err(format!("Cannot assign to constant '{}'", name));
+    }
+
+    let mut value = evaluate_list(expression, session)?;
+    if let Some(op) = op {
+        let current = session
+            .variables
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Unknown variable '{}'", name))?;
+        value = apply_operator(&op.to_string(), &current, &value, session)?;
+    }
+
+    session.variables.insert(name.to_string(), value.clone());
+    Ok(value)
+}
+
+// The right-hand side of `name = ...`: a list for `a, b, c`, or `map(...)`
+fn evaluate_list(expression: &str, session: &Session) -> Result<Value, String> {
+    if let Some(("map", args)) = split_call(expression) {
+        return map_list(&args, session);
+    }
+    match split_top_level(expression) {
+        Some(parts) if parts.len() > 1 => parts
+            .iter()
+            .map(|part| evaluate_expression(part, session))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::List),
+        _ => evaluate_expression(expression, session),
+    }
+}
+
+// `map(list, expr)`: evaluate expr with x bound to each element in turn
+fn map_list(args: &[&str], session: &Session) -> Result<Value, String> {
+    let [list, body] = args else {
+        return Err("Usage: map(list, expression in x)".to_string());
+    };
+    let Value::List(elements) = evaluate_expression(list, session)? else {
+        return Err("The first argument to map must be a list".to_string());
+    };
+    let tree = parse_within_limits(body, session)?;
+    let mut locals = HashMap::new();
+    session.reset_calls();
+    elements
+        .into_iter()
+        .map(|element| {
+            locals.insert("x".to_string(), element);
+            evaluate_scoped(&tree, session, &locals)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Value::List)
+}
+
+// `a, b = 3, 4`: evaluate every right-hand side before assigning any, so
+// `a, b = b, a` swaps. The result is the last value assigned.
+fn assign_all(
+    names: &[&str],
+    expressions: &[&str],
+    session: &mut Session,
+) -> Result<Value, String> {
+    if names.len() != expressions.len() {
+        return Err(format!(
+            "Number of names ({}) and values ({}) differ",
+            names.len(),
+            expressions.len()
+        ));
+    }
+    for (index, name) in names.iter().enumerate() {
+        if session.is_constant(name) {
+            return Err(format!("Cannot assign to constant '{}'", name));
+        }
+        if names[..index].contains(name) {
+            return Err(format!("'{}' is assigned twice", name));
+        }
+    }
+
+    let values = expressions
+        .iter()
+        .map(|expression| evaluate_expression(expression, session))
+        .collect::<Result<Vec<_>, _>>()?;
+    for (name, value) in names.iter().zip(&values) {
+        session.variables.insert(name.to_string(), value.clone());
+    }
+    Ok(values.last().unwrap().clone())
+}
+
+/// Check a statement's syntax without evaluating it: every expression in it
+/// must tokenize and parse. Assignments, function definitions and `at`
+/// bindings are taken apart the way [`evaluate_statement`] reads them.
+///
+/// ```
+/// use calc::{check_statement, Session};
+///
+/// let session = Session::new();
+/// assert_eq!(check_statement("x = y * (2 + z)", &session), Ok(()));
+/// assert!(check_statement("x = 2 +", &session).is_err());
+/// ```
+pub fn check_statement(input: &str, session: &Session) -> Result<(), String> {
+    if let Some((statement, bindings)) = split_at_bindings(input) {
+        for binding in split_top_level(bindings).unwrap_or_default() {
+            match split_assignment(binding) {
+                Some((_, None, expression)) => check_expression(expression, session)?,
+                _ => return Err("Usage: <expression> at name=value, ...".to_string()),
+            }
+        }
+        return check_statement(statement, session);
+    }
+    let input = input.trim_start();
+    let input = input.strip_prefix("const ").unwrap_or(input);
+    if let Some((_, expressions)) = split_multiple_assignment(input) {
+        return expressions
+            .iter()
+            .try_for_each(|expression| check_expression(expression, session));
+    }
+    // `name = a, b, c` assigns a list
+    if let Some((_, _, expression)) = split_assignment(input) {
+        return split_top_level(expression)
+            .unwrap_or_else(|| vec![expression])
+            .iter()
+            .try_for_each(|part| check_expression(part, session));
+    }
+    // `f(x) = body`, or `solve(lhs = rhs, x)` and the like
+    match input.split_once('=') {
+        Some((target, body)) if !body.starts_with('=') && split_call(target).is_some() => {
+            check_expression(body, session)
+        }
+        _ => match split_call(input) {
+            Some(("solve" | "diff", args)) => {
+                args.iter().try_for_each(|arg| match split_equation(arg) {
+                    Some((lhs, rhs)) => {
+                        check_expression(lhs, session)?;
+                        check_expression(rhs, session)
+                    }
+                    None => check_expression(arg, session),
+                })
+            }
+            _ => check_expression(input, session),
+        },
+    }
+}
+
+fn check_expression(expression: &str, session: &Session) -> Result<(), String> {
+    let (tokens, positions) = tokenize_spanned(expression, session)?;
+    parse_spanned(tokens, &positions, session).map(|_| ())
+}
+
+// `const name = expr`: assign once and lock the name against reassignment
+fn define_constant(definition: &str, session: &mut Session) -> Result<Value, String> {
+    let Some((name, None, expression)) = split_assignment(definition) else {
+        return Err("Usage: const name = expression".to_string());
+    };
+    if session.is_constant(name) {
+        return Err(format!("Cannot assign to constant '{}'", name));
+    }
+
+    let value = evaluate_expression(expression, session)?;
+    session.variables.insert(name.to_string(), value.clone());
+    session.constants.insert(name.to_string());
+    Ok(value)
+}
+
+// Evaluate a plain expression, going through the result cache when enabled
+fn evaluate_cached(input: &str, session: &mut Session) -> Result<Value, String> {
+    let key = cache_key(input.trim(), session);
+    if let Some(value) = session.cache.as_mut().and_then(|cache| cache.get(&key)) {
+        // The limits may have been lowered since the result was cached, and
+        // skipping evaluation skips working out whether it is an angle
+        let tree = parse_within_limits(input, session)?;
+        session.angle_result.set(returns_angle(&tree));
+        return Ok(value);
+    }
+
+    let value = evaluate_expression(input, session)?;
+    if session.cache.is_some() && is_cacheable(input.trim(), session) {
+        if let Some(cache) = session.cache.as_mut() {
+            cache.insert(&key, value.clone());
+        }
+    }
+
+    Ok(value)
+}
+
+// Split `name(a, b, ...)` spanning the whole input into the name and its raw
+// argument texts, splitting only on top-level commas
+pub(crate) fn split_call(input: &str) -> Option<(&str, Vec<&str>)> {
+    let input = input.trim();
+    let open = input.find('(')?;
+    let name = input[..open].trim_end();
+    if !is_identifier(name) || !input.ends_with(')') {
+        return None;
+    }
+
+    let args = split_top_level(&input[open + 1..input.len() - 1])?;
+    Some((name, args))
+}
+
+// Split on commas outside parentheses; None if the parentheses don't balance
+fn split_top_level(text: &str) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return None,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return None;
+    }
+    parts.push(&text[start..]);
+
+    Some(parts)
+}
+
+// Split `expr at x=1, y=2` at the first `at` outside brackets. A leading
+// `at` is left alone, so it still works as a variable name.
+fn split_at_bindings(input: &str) -> Option<(&str, &str)> {
+    let stands_alone =
+        |next: Option<char>| !next.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+    let mut depth = 0;
+    for (index, c) in input.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0
+            && !input[..index].trim().is_empty()
+            && input[index..].starts_with("at")
+            && stands_alone(input[..index].chars().next_back())
+            && stands_alone(input[index + 2..].chars().next())
+        {
+            return Some((&input[..index], &input[index + 2..]));
+        }
+    }
+    None
+}
+
+// `expr at x=1, y=2`: evaluate with the bindings overlaid on the variables,
+// leaving them as they were afterwards
+fn evaluate_at(statement: &str, bindings: &str, session: &mut Session) -> Result<Value, String> {
+    let usage = || "Usage: <expression> at name=value, ...".to_string();
+    let mut values = Vec::new();
+    for binding in split_top_level(bindings).ok_or_else(usage)? {
+        let Some((name, None, expression)) = split_assignment(binding) else {
+            return Err(usage());
+        };
+        if session.is_constant(name) {
+            return Err(format!("Cannot assign to constant '{}'", name));
+        }
+        values.push((name.to_string(), evaluate_expression(expression, session)?));
+    }
+
+    let previous: Vec<(String, Option<Value>)> = values
+        .into_iter()
+        .map(|(name, value)| {
+            let old = session.variables.insert(name.clone(), value);
+            (name, old)
+        })
+        .collect();
+    let result = evaluate_statement(statement, session);
+    for (name, old) in previous.into_iter().rev() {
+        match old {
+            Some(old) => session.variables.insert(name, old),
+            None => session.variables.remove(&name),
+        };
+    }
+    result
+}
+
+// Run `f` with `name` temporarily bound to `value`, restoring the previous
+// binding (or its absence) afterwards
+pub(crate) fn with_binding<T>(
+    session: &mut Session,
+    name: &str,
+    value: Value,
+    f: impl FnOnce(&mut Session) -> T,
+) -> T {
+    let previous = session.variables.insert(name.to_string(), value);
+    let result = f(session);
+    match previous {
+        Some(old) => session.variables.insert(name.to_string(), old),
+        None => session.variables.remove(name),
+    };
+    result
+}
+
+// Split `a, b = x, y` into its names and expressions, if the input assigns
+// to more than one name
+fn split_multiple_assignment(input: &str) -> Option<(Vec<&str>, Vec<&str>)> {
+    let (targets, expressions) = input.split_once('=')?;
+    if expressions.starts_with('=') || !targets.contains(',') {
+        return None;
+    }
+
+    let names: Vec<&str> = targets.split(',').map(str::trim).collect();
+    if !names.iter().all(|name| is_identifier(name)) {
+        return None;
+    }
+
+    Some((names, split_top_level(expressions)?))
+}
+
+// Split `name = expr` / `name op= expr` into its parts, if the input is an assignment
+fn split_assignment(input: &str) -> Option<(&str, Option<char>, &str)> {
+    let index = input.find('=')?;
+    let (target, expression) = (&input[..index], &input[index + 1..]);
+    if expression.starts_with('=') {
+        return None;
+    }
+
+    let target = target.trim_end();
+    let (target, op) = match target.chars().last() {
+        Some(c @ ('+' | '-' | '*' | '/')) => (&target[..target.len() - 1], Some(c)),
+        _ => (target, None),
+    };
+
+    let name = target.trim();
+    if !is_identifier(name) {
+        return None;
+    }
+
+    Some((name, op, expression))
+}
@@ -0,0 +1,74 @@
+use crate::eval::apply_operator;
+use crate::evaluate_expression;
+use crate::format::format_result;
+use crate::lexer::is_identifier;
+use crate::session::{with_binding, Session};
+use crate::value::Value;
+
+// Upper bound on the rows one `table` may print
+const MAX_TABLE_ROWS: usize = 1000;
+
+// `table <expr> for x = <from> to <to> [step <step>]`: evaluate the
+// expression at each value of x and print both as two columns
+pub(crate) fn table(input: &str, session: &mut Session) -> Result<String, String> {
+    let usage = || "Usage: table <expression> for x = <from> to <to> [step <step>]".to_string();
+    let rest = input.trim_start().strip_prefix("table").ok_or_else(usage)?;
+    let (expression, range) = rest.rsplit_once(" for ").ok_or_else(usage)?;
+    let (variable, range) = range.split_once('=').ok_or_else(usage)?;
+    let (from, range) = range.split_once(" to ").ok_or_else(usage)?;
+    let (to, step) = match range.split_once(" step ") {
+        Some((to, step)) => (to, Some(step)),
+        None => (range, None),
+    };
+
+    let variable = variable.trim();
+    if !is_identifier(variable) || session.is_constant(variable) {
+        return Err(format!("Invalid variable '{}'", variable));
+    }
+    let from = evaluate_expression(from, session)?;
+    let to = evaluate_expression(to, session)?.as_f64();
+    let step = match step {
+        Some(step) => evaluate_expression(step, session)?,
+        None => Value::Int(1),
+    };
+    if step.as_f64() == 0.0 || (to - from.as_f64()) * step.as_f64() < 0.0 {
+        return Err("The step must move from the start towards the end".to_string());
+    }
+
+    // Each row is start + i * step rather than a running sum, so float steps
+    // such as 0.1 don't drift; a little slack lets them still reach the end
+    let direction = step.as_f64().signum();
+    let end = to + step.as_f64().abs() * 1e-9 * direction;
+    let mut rows = Vec::new();
+    for index in 0.. {
+        let offset = apply_operator("*", &Value::Int(index), &step, session)?;
+        let x = apply_operator("+", &from, &offset, session)?;
+        if (end - x.as_f64()) * direction < 0.0 {
+            break;
+        }
+        if rows.len() == MAX_TABLE_ROWS {
+            return Err(format!(
+                "Table exceeds the limit of {} rows",
+                MAX_TABLE_ROWS
+            ));
+        }
+        let y = with_binding(session, variable, x.clone(), |session| {
+            evaluate_expression(expression, session)
+        })?;
+        rows.push((format_result(&x, session)?, format_result(&y, session)?));
+    }
+
+    let header = (variable.to_string(), expression.trim().to_string());
+    let width = rows
+        .iter()
+        .map(|(x, _)| x.chars().count())
+        .chain([header.0.chars().count()])
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<String> = [header]
+        .iter()
+        .chain(&rows)
+        .map(|(x, y)| format!("{:<width$}  {}", x, y, width = width))
+        .collect();
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,126 @@
+use crate::evaluate_expression;
+use crate::format::format_result;
+use crate::session::Session;
+use crate::value::Value;
+
+// A unit as the affine map to its dimension's base unit (m, kg or K):
+// base = value * scale + offset. Only temperatures have an offset.
+struct Unit {
+    name: &'static str,
+    dimension: &'static str,
+    scale: f64,
+    offset: f64,
+}
+
+const UNITS: &[Unit] = &[
+    Unit {
+        name: "m",
+        dimension: "length",
+        scale: 1.0,
+        offset: 0.0,
+    },
+    Unit {
+        name: "km",
+        dimension: "length",
+        scale: 1000.0,
+        offset: 0.0,
+    },
+    Unit {
+        name: "mi",
+        dimension: "length",
+        scale: 1609.344,
+        offset: 0.0,
+    },
+    Unit {
+        name: "ft",
+        dimension: "length",
+        scale: 0.3048,
+        offset: 0.0,
+    },
+    Unit {
+        name: "kg",
+        dimension: "mass",
+        scale: 1.0,
+        offset: 0.0,
+    },
+    Unit {
+        name: "g",
+        dimension: "mass",
+        scale: 0.001,
+        offset: 0.0,
+    },
+    Unit {
+        name: "lb",
+        dimension: "mass",
+        scale: 0.45359237,
+        offset: 0.0,
+    },
+    Unit {
+        name: "K",
+        dimension: "temperature",
+        scale: 1.0,
+        offset: 0.0,
+    },
+    Unit {
+        name: "C",
+        dimension: "temperature",
+        scale: 1.0,
+        offset: 273.15,
+    },
+    Unit {
+        name: "F",
+        dimension: "temperature",
+        scale: 5.0 / 9.0,
+        offset: 459.67 * 5.0 / 9.0,
+    },
+];
+
+// `convert <expr> <from> <to>`: evaluate the expression as an amount in one
+// unit and print it in another. The converted amount becomes `ans`.
+pub(crate) fn convert(input: &str, session: &mut Session) -> Result<String, String> {
+    let usage = || "Usage: convert <value> <from> <to>".to_string();
+    let rest = input.trim().strip_prefix("convert").ok_or_else(usage)?;
+    let (rest, to) = rest
+        .trim_end()
+        .rsplit_once(char::is_whitespace)
+        .ok_or_else(usage)?;
+    let (expression, from) = rest
+        .trim_end()
+        .rsplit_once(char::is_whitespace)
+        .ok_or_else(usage)?;
+    if expression.trim().is_empty() {
+        return Err(usage());
+    }
+
+    let (from, to) = (unit(from)?, unit(to)?);
+    if from.dimension != to.dimension {
+        return Err(format!(
+            "Cannot convert {} ({}) to {} ({})",
+            from.name, from.dimension, to.name, to.dimension
+        ));
+    }
+
+    let amount = evaluate_expression(expression, session)?.as_f64();
+    let base = amount * from.scale + from.offset;
+    // 15 significant digits, so -40 C is -40 F rather than showing the
+    // rounding error of going through kelvin
+    let converted = (base - to.offset) / to.scale;
+    let converted = match format!("{:.14e}", converted).parse() {
+        Ok(rounded) if converted.is_finite() => Value::Float(rounded),
+        _ => Value::Float(converted),
+    };
+    let result = format!("{} {}", format_result(&converted, session)?, to.name);
+    session.ans = converted;
+    Ok(result)
+}
+
+fn unit(name: &str) -> Result<&'static Unit, String> {
+    UNITS.iter().find(|unit| unit.name == name).ok_or_else(|| {
+        let names: Vec<&str> = UNITS.iter().map(|unit| unit.name).collect();
+        format!(
+            "Unknown unit '{}' (expected one of {})",
+            name,
+            names.join(", ")
+        )
+    })
+}
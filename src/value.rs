@@ -0,0 +1,106 @@
+use crate::complex::Complex;
+
+/// A computed result. Integer arithmetic stays exact as `Int` while it fits
+/// in an `i64` and divides evenly, and promotes to `Float` otherwise. In
+/// `mode frac`, division that doesn't come out even gives an exact `Ratio`
+/// of numerator and denominator instead. Comparisons and logical operators
+/// produce `Bool`, which counts as 1 or 0 wherever a number is needed.
+/// `mode complex` adds `Complex`, for results with an imaginary part, and
+/// `name = 1, 2, 3` stores a `List`.
+///
+/// ```
+/// use calc::{evaluate_expression, Session, Value};
+///
+/// let session = Session::new();
+/// assert_eq!(
+///     evaluate_expression("2^60", &session),
+///     Ok(Value::Int(1_152_921_504_606_846_976))
+/// );
+/// assert_eq!(evaluate_expression("7 / 2", &session), Ok(Value::Float(3.5)));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    // In lowest terms, with a denominator above 1
+    Ratio(i64, i64),
+    Float(f64),
+    Bool(bool),
+    Complex(Complex),
+    // Assigned as `name = a, b, c`; only aggregates and `map` take one
+    List(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Value::Int(n) => *n as f64,
+            Value::Ratio(n, d) => *n as f64 / *d as f64,
+            Value::Float(x) => *x,
+            Value::Bool(true) => 1.0,
+            Value::Bool(false) => 0.0,
+            // No real number stands for it, so real-only operations give NaN
+            Value::Complex(_) | Value::List(_) => f64::NAN,
+        }
+    }
+
+    /// The kind of value, as `mode verbose` labels results.
+    ///
+    /// ```
+    /// use calc::{evaluate_expression, Session};
+    ///
+    /// let session = Session::new();
+    /// let kind = |input| evaluate_expression(input, &session).unwrap().type_name();
+    /// assert_eq!(kind("2 + 3 * 4"), "int");
+    /// assert_eq!(kind("6 / 2"), "int");
+    /// assert_eq!(kind("7 / 2"), "float");
+    /// assert_eq!(kind("1 < 2"), "bool");
+    /// ```
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Ratio(..) => "fraction",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::Complex(_) => "complex",
+            Value::List(_) => "list",
+        }
+    }
+
+    // The exact integer, if this is a whole number
+    pub(crate) fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(n) => Some(*n),
+            Value::Ratio(..) | Value::Float(_) | Value::Complex(_) | Value::List(_) => None,
+            Value::Bool(b) => Some(*b as i64),
+        }
+    }
+
+    // The exact value as numerator and denominator, if this is not a float
+    pub(crate) fn as_ratio(&self) -> Option<(i64, i64)> {
+        match self {
+            Value::Ratio(n, d) => Some((*n, *d)),
+            _ => Some((self.as_int()?, 1)),
+        }
+    }
+
+    // Anything other than zero counts as true
+    pub(crate) fn is_truthy(&self) -> bool {
+        match self {
+            Value::Complex(z) => z.re != 0.0 || z.im != 0.0,
+            Value::List(values) => !values.is_empty(),
+            _ => self.as_f64() != 0.0,
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Value::Float(x)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Int(n)
+    }
+}
@@ -0,0 +1,171 @@
+// The calculator binary run as a script would run it, with input piped in
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn run(args: &[&str], input: &str) -> Output {
+    // An empty HOME keeps a developer's own .calcrc out of the way
+    let mut child = Command::new(env!("CARGO_BIN_EXE_my-project"))
+        .args(args)
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .env_remove("CALC_CURRENCY")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start the calculator");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn ans_carries_from_one_line_to_the_next() {
+    let output = run(&[], "ans\n2 + 3\nans * 2\n1 / 0 +\nans + 1\n");
+    let lines: Vec<String> = stdout(&output).lines().map(String::from).collect();
+    assert_eq!(lines[..3], ["Result: 0", "Result: 5", "Result: 10"]);
+    assert!(lines[3].starts_with("Error: "));
+    // The failed line left `ans` at 10
+    assert_eq!(lines[4], "Result: 11");
+}
+
+#[test]
+fn out_receives_only_the_final_result() {
+    let path = format!("{}/out.txt", env!("CARGO_TARGET_TMPDIR"));
+    let output = run(&["--print-last-only", "--out", &path], "2 + 3\nans * 4\n");
+    assert_eq!(stdout(&output), "20\n");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "20\n");
+}
+
+#[test]
+fn stdin_once_evaluates_only_the_first_line() {
+    let output = run(&["--stdin-once"], "6 * 7\n1 + 1\n");
+    assert_eq!(stdout(&output), "42\n");
+    assert!(output.status.success());
+
+    let output = run(&["--stdin-once"], "6 *\n");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn flags_override_the_config_file() {
+    let path = format!("{}/calcrc", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&path, "precision = 2\nangle = deg\n").unwrap();
+
+    let output = run(&["--config", &path], "1 / 3\nsin(90)\n");
+    assert_eq!(stdout(&output), "Result: 0.33\nResult: 1\n");
+    let output = run(&["--config", &path, "--precision", "4"], "1 / 3\n");
+    assert_eq!(stdout(&output), "Result: 0.3333\n");
+}
+
+#[test]
+fn interactive_shows_prompts_for_piped_input() {
+    let output = run(&[], "1 + 1\n");
+    assert_eq!(stdout(&output), "Result: 2\n");
+
+    let output = run(&["--interactive"], "1 + 1\nquit\n");
+    let text = stdout(&output);
+    assert!(text.starts_with("Welcome"));
+    assert!(text.contains("Enter an expression"));
+    assert!(text.contains("Result: 2\n"));
+    assert!(text.ends_with("Goodbye!\n"));
+}
+
+#[test]
+fn no_banner_and_short_prompt() {
+    let output = run(
+        &["--interactive", "--no-banner", "--short-prompt"],
+        "1 + 1
+quit
+",
+    );
+    let text = stdout(&output);
+    assert!(!text.starts_with("Welcome"));
+    assert!(!text.contains("Enter an expression"));
+    assert!(text.contains("> "));
+    assert!(text.contains("Result: 2\n"));
+}
+
+#[test]
+fn dot_and_bang_bang_rerun_the_last_expression() {
+    let output = run(&[], ".\nx = 2\nx = x * 3\n.\n!!\n");
+    let lines: Vec<String> = stdout(&output).lines().map(String::from).collect();
+    assert!(lines[0].starts_with("Error: No previous expression"));
+    assert_eq!(
+        lines[1..],
+        ["Result: 2", "Result: 6", "Result: 18", "Result: 54"]
+    );
+}
+
+#[test]
+fn trailing_semicolon_suppresses_output() {
+    let output = run(&[], "x = 5;\nx = 5; x + 1\ny = 2; y * x;\nans\n");
+    assert_eq!(stdout(&output), "Result: 6\nResult: 10\n");
+}
+
+#[test]
+fn profile_reports_each_stage_on_stderr() {
+    let output = run(&["--profile"], "2 + 3\n");
+    assert_eq!(stdout(&output), "Result: 5\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let stages: Vec<&str> = stderr
+        .lines()
+        .map(|line| line.rsplit_once(' ').unwrap().0)
+        .collect();
+    assert_eq!(
+        stages,
+        ["profile: tokenize", "profile: parse", "profile: evaluate"]
+    );
+}
+
+#[test]
+fn failed_assert_fails_the_script() {
+    let output = run(&[], "assert(1 + 1, 2)\nassert(0.1 + 0.2, 0.3)\n");
+    assert_eq!(stdout(&output), "OK\nOK\n");
+    assert!(output.status.success());
+
+    // The script carries on after the failure, but exits with status 1
+    let output = run(&[], "assert(2, 3)\n4 + 4\n");
+    assert_eq!(
+        stdout(&output),
+        "Error: Assertion failed: 2 is 2, expected 3\nResult: 8\n"
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn log_appends_each_statement_and_its_outcome() {
+    let path = format!("{}/calc.log", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_file(&path);
+
+    run(&["--log", &path], "2 + 3\n2 +\n");
+    let first = "2 + 3 => 5\n2 + => Error: Expected a number or '(' but found end of input\n";
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), first);
+
+    run(&["--log", &path], "6 * 7\n");
+    let log = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(log, format!("{}6 * 7 => 42\n", first));
+}
+
+#[test]
+fn check_only_reports_bad_lines_without_evaluating() {
+    let output = run(&["--check-only"], "1 + 1\n2 +\n");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "line 2: Expected a number or '(' but found end of input\n"
+    );
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run(&["--check-only"], "1 + 1\nx = 2 * 3\n");
+    assert_eq!(stdout(&output), "");
+    assert!(output.status.success());
+}